@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rox::cst;
+
+// The concrete syntax tree must print back to exactly the source it came from, whatever the
+// input.
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    assert_eq!(cst::parse(&source).to_string(), source);
+});
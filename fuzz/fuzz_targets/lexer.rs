@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rox::lexer::Lexer;
+
+// Lexing any input must terminate without panicking.
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    for _token in Lexer::from_source(&source) {}
+});
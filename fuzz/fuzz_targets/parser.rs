@@ -0,0 +1,29 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rox::lexer::Lexer;
+use rox::parser::Parser;
+use rox::resolver::Resolver;
+
+// Each input byte picks a token, so the fuzzer explores token streams rather than spending
+// its time getting past the lexer.
+const VOCABULARY: &[&str] = &[
+    "(", ")", "{", "}", "[", "]", ",", ":", ".", "-", "+", ";", "/", "*", "%", "!", "!=", "=",
+    "==", ">", ">=", "<", "<=", "a", "b", "init", "\"s\"", "1", "2.5", "and", "as", "break",
+    "class", "catch", "continue", "else", "false", "finally", "from", "fun", "for", "if", "import",
+    "in", "nil", "or", "print", "return", "super", "this", "throw", "true", "try", "var", "while",
+];
+
+// Parsing and resolving any token stream must report errors without panicking.
+fuzz_target!(|data: &[u8]| {
+    let tokens: Vec<&str> = data
+        .iter()
+        .map(|byte| VOCABULARY[*byte as usize % VOCABULARY.len()])
+        .collect();
+    let source = tokens.join(" ");
+
+    let mut lexer = Lexer::from_source(&source);
+    if let Ok(statements) = Parser::new(&mut lexer).parse() {
+        let _ = Resolver::new().resolve(&statements);
+    }
+});
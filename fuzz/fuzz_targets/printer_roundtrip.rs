@@ -0,0 +1,22 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rox::lexer::Lexer;
+use rox::parser::Parser;
+use rox::printer;
+
+// Whatever parses must print to source that parses again and prints identically.
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    let mut lexer = Lexer::from_source(&source);
+    let Ok(statements) = Parser::new(&mut lexer).parse() else {
+        return;
+    };
+
+    let printed = printer::print_program(&statements);
+    let mut lexer = Lexer::from_source(&printed);
+    let reparsed = Parser::new(&mut lexer)
+        .parse()
+        .unwrap_or_else(|errors| panic!("printed source does not parse: {:?}\n{}", errors, printed));
+    assert_eq!(printed, printer::print_program(&reparsed));
+});
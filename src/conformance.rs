@@ -0,0 +1,218 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use crate::interpreter::CapturedOutput;
+use crate::interpreter::INTERPRETER_STACK_SIZE;
+use crate::interpreter::Interpreter;
+use crate::interpreter::Limits;
+use crate::lexer::EXIT_COMPILE_ERROR;
+use crate::lexer::EXIT_RUNTIME_ERROR;
+use crate::lexer::RoxError;
+
+/// What a test file expects, read from its comments in the style of the Crafting Interpreters
+/// test suite:
+///
+/// - `// expect: <output>` expects a line of stdout.
+/// - `// expect runtime error: <message>` expects the script to fail at that line.
+/// - `// [line N] Error at ...` or `// Error at ...` expects a compile error, on line N or
+///   on the comment's own line.
+#[derive(Debug, Default, PartialEq)]
+pub struct Expectations {
+    pub output: Vec<String>,
+    pub compile_errors: Vec<String>,
+    pub runtime_error: Option<(String, usize)>,
+}
+
+impl Expectations {
+    pub fn parse(source: &str) -> Expectations {
+        let mut expectations = Expectations::default();
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+
+            if let Some((_, output)) = line.split_once("// expect: ") {
+                expectations.output.push(output.to_string());
+            } else if let Some((_, message)) = line.split_once("// expect runtime error: ") {
+                expectations.runtime_error = Some((message.to_string(), line_number));
+            } else if let Some((_, error)) = line.split_once("// Error") {
+                expectations
+                    .compile_errors
+                    .push(format!("[line {}] Error{}", line_number, error));
+            } else if let Some((_, error)) = line
+                .split_once("// [line ")
+                .or_else(|| line.split_once("// [java line "))
+            {
+                expectations.compile_errors.push(format!("[line {}", error));
+            }
+        }
+        expectations
+    }
+
+    fn exit_code(&self) -> i32 {
+        if !self.compile_errors.is_empty() {
+            EXIT_COMPILE_ERROR
+        } else if self.runtime_error.is_some() {
+            EXIT_RUNTIME_ERROR
+        } else {
+            0
+        }
+    }
+}
+
+/// What running a script produced, as `rox run` would have printed it.
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32,
+}
+
+/// Runs `source` in a fresh interpreter on its own thread, capturing everything it prints.
+pub fn run_source(source: String) -> Outcome {
+    run(source, None, false)
+}
+
+/// Runs the script at `path` like `run_source`, with its imports relative to the file.
+pub fn run_path(path: &Path, optimize: bool) -> Result<Outcome, io::Error> {
+    let source = fs::read_to_string(path)?;
+    Ok(run(source, Some(path.to_path_buf()), optimize))
+}
+
+fn run(source: String, path: Option<PathBuf>, optimize: bool) -> Outcome {
+    let runner = thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(move || {
+            let output = CapturedOutput::new();
+            let mut interpreter = Interpreter::new();
+            interpreter.set_output(output.clone());
+            interpreter.set_limits(Limits::for_stack_size(INTERPRETER_STACK_SIZE));
+            interpreter.set_optimize(optimize);
+            if let Some(path) = path {
+                interpreter.set_script_path(&path);
+            }
+            let result = interpreter.eval(&source);
+
+            let (stderr, exit_code) = match result {
+                Ok(_) => (String::new(), 0),
+                Err(error @ RoxError::Exit(_)) => (String::new(), error.exit_code()),
+                Err(error) => (format!("{}\n", error), error.exit_code()),
+            };
+            Outcome {
+                stdout: output.take_string(),
+                stderr,
+                exit_code,
+            }
+        })
+        .expect("failed to spawn interpreter thread");
+    runner
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+/// Compares an outcome against the expectations, returning one message per mismatch.
+pub fn check(expectations: &Expectations, outcome: &Outcome) -> Vec<String> {
+    let mut failures = Vec::new();
+
+    let actual_output: Vec<&str> = outcome.stdout.lines().collect();
+    if actual_output != expectations.output {
+        failures.push("Output differs:".to_string());
+        failures.extend(diff_lines(&expectations.output, &actual_output));
+    }
+
+    let stderr: Vec<&str> = outcome.stderr.lines().collect();
+    if !expectations.compile_errors.is_empty() {
+        if stderr != expectations.compile_errors {
+            failures.push("Compile errors differ:".to_string());
+            failures.extend(diff_lines(&expectations.compile_errors, &stderr));
+        }
+    } else if let Some((message, line)) = &expectations.runtime_error {
+        if stderr.first() != Some(&message.as_str()) {
+            failures.push(format!(
+                "Expected runtime error '{}' but got '{}'.",
+                message,
+                stderr.first().unwrap_or(&"")
+            ));
+        }
+        let expected_line = format!("[line {}]", line);
+        if !stderr.get(1).is_some_and(|l| l.starts_with(&expected_line)) {
+            failures.push(format!(
+                "Expected runtime error on line {} but got '{}'.",
+                line,
+                stderr.get(1).unwrap_or(&"")
+            ));
+        }
+    } else if !stderr.is_empty() {
+        failures.push("Unexpected errors:".to_string());
+        failures.extend(stderr.iter().map(|l| format!("  {}", l)));
+    }
+
+    if outcome.exit_code != expectations.exit_code() {
+        failures.push(format!(
+            "Expected exit code {} but got {}.",
+            expectations.exit_code(),
+            outcome.exit_code
+        ));
+    }
+    failures
+}
+
+// A line-by-line diff, enough to spot where the output starts to go wrong.
+fn diff_lines(expected: &[String], actual: &[&str]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => lines.push(format!("    {}", e)),
+            (e, a) => {
+                if let Some(e) = e {
+                    lines.push(format!("  - {}", e));
+                }
+                if let Some(a) = a {
+                    lines.push(format!("  + {}", a));
+                }
+            }
+        }
+    }
+    lines
+}
+
+pub struct TestResult {
+    pub path: PathBuf,
+    pub failures: Vec<String>,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+pub fn run_file(path: &Path) -> Result<TestResult, io::Error> {
+    let source = fs::read_to_string(path)?;
+    let expectations = Expectations::parse(&source);
+    let outcome = run(source, Some(path.to_path_buf()), false);
+    Ok(TestResult {
+        path: path.to_path_buf(),
+        failures: check(&expectations, &outcome),
+    })
+}
+
+/// Runs every `.lox` file under `dir`, in path order.
+pub fn run_dir(dir: &Path) -> Result<Vec<TestResult>, io::Error> {
+    let mut files = Vec::new();
+    collect_lox_files(dir, &mut files)?;
+    files.sort();
+    files.iter().map(|path| run_file(path)).collect()
+}
+
+fn collect_lox_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), io::Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_lox_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "lox") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
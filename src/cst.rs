@@ -0,0 +1,745 @@
+use std::fmt::Formatter;
+
+use crate::lexer::Lexer;
+use crate::lexer::TokenType;
+
+// Same limits as the AST parser. Anything nested deeper becomes an error node.
+const MAX_NESTING: usize = 200;
+const MAX_ELSE_BRANCHES: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    Program,
+
+    // Declarations and statements.
+    ClassDecl,
+    FunDecl,
+    VarDecl,
+    ImportDecl,
+    Function,
+    Params,
+    ExprStmt,
+    PrintStmt,
+    ReturnStmt,
+    BreakStmt,
+    ThrowStmt,
+    TryStmt,
+    Catch,
+    Finally,
+    ContinueStmt,
+    IfStmt,
+    WhileStmt,
+    ForStmt,
+    ForInStmt,
+    Block,
+
+    // Expressions.
+    Assign,
+    Logical,
+    Binary,
+    Unary,
+    Call,
+    Args,
+    Get,
+    Index,
+    Grouping,
+    Literal,
+    List,
+    Map,
+    Interpolation,
+    Name,
+    This,
+    Super,
+
+    // Tokens the parser couldn't make sense of.
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    Newline,
+    Comment,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+}
+
+/// A token together with the exact source text around it. Trivia on the same line after a
+/// token is its trailing trivia, everything else leads the next token. The last token of a
+/// tree is always an `Eof` token holding whatever trivia ends the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxToken {
+    pub kind: TokenType,
+    pub text: String,
+    pub line: usize,
+    pub column: usize,
+    pub leading: Vec<Trivia>,
+    pub trailing: Vec<Trivia>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+/// A node of the concrete syntax tree. Unlike the AST it keeps every token, including
+/// punctuation, trivia and tokens that don't parse, so printing a tree with `Display` gives
+/// back the source it was parsed from byte for byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxNode {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    pub fn child_nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    pub fn child_tokens(&self) -> impl Iterator<Item = &SyntaxToken> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Token(token) => Some(token),
+            SyntaxElement::Node(_) => None,
+        })
+    }
+
+    /// All tokens under this node, in source order.
+    pub fn tokens(&self) -> Vec<&SyntaxToken> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a SyntaxToken>) {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.collect_tokens(tokens),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for SyntaxToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        for trivia in &self.leading {
+            write!(f, "{}", trivia.text)?;
+        }
+        write!(f, "{}", self.text)?;
+        for trivia in &self.trailing {
+            write!(f, "{}", trivia.text)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        for token in self.tokens() {
+            write!(f, "{}", token)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits source into tokens with their trivia attached, ending with an `Eof` token.
+pub fn tokenize(source: &str) -> Vec<SyntaxToken> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut lexer = Lexer::from_source(source).with_trivia();
+    let mut tokens: Vec<SyntaxToken> = Vec::new();
+    let mut leading = Vec::new();
+    // Whether a newline came since the last token, after which trivia leads the next one.
+    let mut line_ended = true;
+
+    loop {
+        let start = lexer.offset();
+        let Some(token) = lexer.next() else {
+            break;
+        };
+        let text: String = chars[start..lexer.offset()].iter().collect();
+
+        let trivia_kind = match token.token_type {
+            TokenType::Whitespace => Some(TriviaKind::Whitespace),
+            TokenType::Newline => Some(TriviaKind::Newline),
+            TokenType::Comment => Some(TriviaKind::Comment),
+            _ => None,
+        };
+        match trivia_kind {
+            Some(kind) => {
+                let trivia = Trivia { kind, text };
+                match tokens.last_mut() {
+                    Some(last) if !line_ended && kind != TriviaKind::Newline => {
+                        last.trailing.push(trivia)
+                    }
+                    _ => {
+                        line_ended |= kind == TriviaKind::Newline;
+                        leading.push(trivia);
+                    }
+                }
+            }
+            None => {
+                tokens.push(SyntaxToken {
+                    kind: token.token_type,
+                    text,
+                    line: token.line,
+                    column: token.column,
+                    leading: std::mem::take(&mut leading),
+                    trailing: Vec::new(),
+                });
+                line_ended = false;
+            }
+        }
+    }
+
+    let (line, column) = end_position(&chars);
+    tokens.push(SyntaxToken {
+        kind: TokenType::Eof,
+        text: String::new(),
+        line,
+        column,
+        leading,
+        trailing: Vec::new(),
+    });
+    tokens
+}
+
+// The line and column just past the end of the source.
+fn end_position(chars: &[char]) -> (usize, usize) {
+    let line = 1 + chars.iter().filter(|c| **c == '\n').count();
+    let column = 1 + chars.iter().rev().take_while(|c| **c != '\n').count();
+    (line, column)
+}
+
+/// Parses source into a concrete syntax tree. This never fails, anything that doesn't fit
+/// the grammar ends up in `Error` nodes, so tools can work on code that is being edited.
+/// Use the AST parser to find out what the errors are.
+pub fn parse(source: &str) -> SyntaxNode {
+    let mut parser = CstParser {
+        tokens: tokenize(source),
+        position: 0,
+        depth: 0,
+        else_depth: 0,
+    };
+    let mut children = Vec::new();
+    while parser.peek() != TokenType::Eof {
+        parser.declaration_or_error(&mut children);
+    }
+    parser.bump(&mut children);
+    SyntaxNode {
+        kind: SyntaxKind::Program,
+        children,
+    }
+}
+
+struct CstParser {
+    tokens: Vec<SyntaxToken>,
+    position: usize,
+    depth: usize,
+    else_depth: usize,
+}
+
+impl CstParser {
+    fn peek(&self) -> TokenType {
+        self.tokens[self.position].kind
+    }
+
+    // The kind of the token `n` places after the next one.
+    fn peek_ahead(&self, n: usize) -> TokenType {
+        self.tokens
+            .get(self.position + n)
+            .map_or(TokenType::Eof, |token| token.kind)
+    }
+
+    fn bump(&mut self, children: &mut Vec<SyntaxElement>) {
+        if let Some(token) = self.tokens.get(self.position) {
+            children.push(SyntaxElement::Token(token.clone()));
+            if token.kind != TokenType::Eof {
+                self.position += 1;
+            }
+        }
+    }
+
+    // Consumes the next token if it is of the given kind. A missing token is left out of the
+    // tree rather than reported.
+    fn eat(&mut self, kind: TokenType, children: &mut Vec<SyntaxElement>) -> bool {
+        if self.peek() == kind {
+            self.bump(children);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error_node(&mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        if self.peek() != TokenType::Eof {
+            self.bump(&mut children);
+        }
+        node(SyntaxKind::Error, children)
+    }
+
+    // A declaration that is guaranteed to consume at least one token, so loops over
+    // declarations always make progress.
+    fn declaration_or_error(&mut self, children: &mut Vec<SyntaxElement>) {
+        let start = self.position;
+        let declaration = self.declaration();
+        children.push(SyntaxElement::Node(declaration));
+        if self.position == start {
+            children.push(SyntaxElement::Node(self.error_node()));
+        }
+    }
+
+    fn declaration(&mut self) -> SyntaxNode {
+        if self.depth >= MAX_NESTING {
+            return self.error_node();
+        }
+        self.depth += 1;
+        let declaration = match self.peek() {
+            TokenType::Class => self.class_declaration(),
+            TokenType::Fun => {
+                let mut children = Vec::new();
+                self.bump(&mut children);
+                children.push(SyntaxElement::Node(self.function()));
+                node(SyntaxKind::FunDecl, children)
+            }
+            TokenType::Var => self.var_declaration(),
+            TokenType::Import | TokenType::From => self.import_declaration(),
+            _ => self.statement(),
+        };
+        self.depth -= 1;
+        declaration
+    }
+
+    fn class_declaration(&mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        self.eat(TokenType::Identifier, &mut children);
+        if self.eat(TokenType::Less, &mut children) && self.peek() == TokenType::Identifier {
+            let mut name = Vec::new();
+            self.bump(&mut name);
+            children.push(SyntaxElement::Node(node(SyntaxKind::Name, name)));
+        }
+        if self.eat(TokenType::LeftBrace, &mut children) {
+            while !matches!(self.peek(), TokenType::RightBrace | TokenType::Eof) {
+                if self.peek() == TokenType::Identifier {
+                    children.push(SyntaxElement::Node(self.function()));
+                } else {
+                    children.push(SyntaxElement::Node(self.error_node()));
+                }
+            }
+            self.eat(TokenType::RightBrace, &mut children);
+        }
+        node(SyntaxKind::ClassDecl, children)
+    }
+
+    // A function's name, parameters and body, shared by `fun` declarations and methods.
+    fn function(&mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        self.eat(TokenType::Identifier, &mut children);
+        if self.peek() == TokenType::LeftParen {
+            let mut params = Vec::new();
+            self.bump(&mut params);
+            while self.eat(TokenType::Identifier, &mut params)
+                && self.eat(TokenType::Comma, &mut params)
+            {}
+            self.eat(TokenType::RightParen, &mut params);
+            children.push(SyntaxElement::Node(node(SyntaxKind::Params, params)));
+        }
+        if self.peek() == TokenType::LeftBrace {
+            children.push(SyntaxElement::Node(self.block()));
+        }
+        node(SyntaxKind::Function, children)
+    }
+
+    fn var_declaration(&mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        self.eat(TokenType::Identifier, &mut children);
+        if self.eat(TokenType::Equal, &mut children) {
+            children.push(SyntaxElement::Node(self.expression()));
+        }
+        self.eat(TokenType::Semicolon, &mut children);
+        node(SyntaxKind::VarDecl, children)
+    }
+
+    // `import "path" as name;` or `from "path" import a, b;`.
+    fn import_declaration(&mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        self.eat(TokenType::String, &mut children);
+        if self.eat(TokenType::Import, &mut children) {
+            while self.eat(TokenType::Identifier, &mut children)
+                && self.eat(TokenType::Comma, &mut children)
+            {}
+        } else if self.eat(TokenType::Identifier, &mut children) {
+            self.eat(TokenType::Identifier, &mut children);
+        }
+        self.eat(TokenType::Semicolon, &mut children);
+        node(SyntaxKind::ImportDecl, children)
+    }
+
+    fn statement(&mut self) -> SyntaxNode {
+        match self.peek() {
+            TokenType::Print => self.keyword_statement(SyntaxKind::PrintStmt),
+            TokenType::Return => self.keyword_statement(SyntaxKind::ReturnStmt),
+            TokenType::Throw => self.keyword_statement(SyntaxKind::ThrowStmt),
+            TokenType::Try => self.try_statement(),
+            TokenType::Break | TokenType::Continue => {
+                let kind = if self.peek() == TokenType::Break {
+                    SyntaxKind::BreakStmt
+                } else {
+                    SyntaxKind::ContinueStmt
+                };
+                let mut children = Vec::new();
+                self.bump(&mut children);
+                self.eat(TokenType::Semicolon, &mut children);
+                node(kind, children)
+            }
+            TokenType::LeftBrace => self.block(),
+            TokenType::If => {
+                let mut children = Vec::new();
+                self.bump(&mut children);
+                self.condition(&mut children);
+                self.body(&mut children);
+                if self.eat(TokenType::Else, &mut children) {
+                    if self.else_depth >= MAX_ELSE_BRANCHES {
+                        children.push(SyntaxElement::Node(self.error_node()));
+                    } else {
+                        // An `else if` chain isn't nesting, the branches sit side by side.
+                        let depth = self.depth;
+                        self.depth = depth.saturating_sub(1);
+                        self.else_depth += 1;
+                        self.body(&mut children);
+                        self.else_depth -= 1;
+                        self.depth = depth;
+                    }
+                }
+                node(SyntaxKind::IfStmt, children)
+            }
+            TokenType::While => {
+                let mut children = Vec::new();
+                self.bump(&mut children);
+                self.condition(&mut children);
+                self.body(&mut children);
+                node(SyntaxKind::WhileStmt, children)
+            }
+            TokenType::For => self.for_statement(),
+            _ => {
+                let mut children = Vec::new();
+                if !matches!(self.peek(), TokenType::Semicolon) {
+                    children.push(SyntaxElement::Node(self.expression()));
+                }
+                self.eat(TokenType::Semicolon, &mut children);
+                node(SyntaxKind::ExprStmt, children)
+            }
+        }
+    }
+
+    // `print` or `return`, an optional expression and the semicolon.
+    fn keyword_statement(&mut self, kind: SyntaxKind) -> SyntaxNode {
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        if !matches!(
+            self.peek(),
+            TokenType::Semicolon | TokenType::RightBrace | TokenType::Eof
+        ) {
+            children.push(SyntaxElement::Node(self.expression()));
+        }
+        self.eat(TokenType::Semicolon, &mut children);
+        node(kind, children)
+    }
+
+    fn condition(&mut self, children: &mut Vec<SyntaxElement>) {
+        self.eat(TokenType::LeftParen, children);
+        children.push(SyntaxElement::Node(self.expression()));
+        self.eat(TokenType::RightParen, children);
+    }
+
+    fn body(&mut self, children: &mut Vec<SyntaxElement>) {
+        if !matches!(self.peek(), TokenType::RightBrace | TokenType::Eof) {
+            self.declaration_or_error(children);
+        }
+    }
+
+    fn for_statement(&mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        if self.eat(TokenType::LeftParen, &mut children) {
+            if self.peek() == TokenType::Var && self.peek_ahead(2) == TokenType::In {
+                self.bump(&mut children);
+                self.bump(&mut children);
+                self.bump(&mut children);
+                children.push(SyntaxElement::Node(self.expression()));
+                self.eat(TokenType::RightParen, &mut children);
+                self.body(&mut children);
+                return node(SyntaxKind::ForInStmt, children);
+            }
+            match self.peek() {
+                TokenType::Semicolon => self.bump(&mut children),
+                TokenType::Var => children.push(SyntaxElement::Node(self.var_declaration())),
+                _ => children.push(SyntaxElement::Node(self.statement())),
+            }
+            if self.peek() != TokenType::Semicolon {
+                children.push(SyntaxElement::Node(self.expression()));
+            }
+            self.eat(TokenType::Semicolon, &mut children);
+            if self.peek() != TokenType::RightParen {
+                children.push(SyntaxElement::Node(self.expression()));
+            }
+            self.eat(TokenType::RightParen, &mut children);
+        }
+        self.body(&mut children);
+        node(SyntaxKind::ForStmt, children)
+    }
+
+    fn try_statement(&mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        self.block_if_present(&mut children);
+        if self.peek() == TokenType::Catch {
+            let mut catch = Vec::new();
+            self.bump(&mut catch);
+            if self.eat(TokenType::LeftParen, &mut catch) {
+                self.eat(TokenType::Identifier, &mut catch);
+                self.eat(TokenType::RightParen, &mut catch);
+            }
+            self.block_if_present(&mut catch);
+            children.push(SyntaxElement::Node(node(SyntaxKind::Catch, catch)));
+        }
+        if self.peek() == TokenType::Finally {
+            let mut finally = Vec::new();
+            self.bump(&mut finally);
+            self.block_if_present(&mut finally);
+            children.push(SyntaxElement::Node(node(SyntaxKind::Finally, finally)));
+        }
+        node(SyntaxKind::TryStmt, children)
+    }
+
+    // The block that must follow `try`, `catch` and `finally`, left out when it is missing.
+    fn block_if_present(&mut self, children: &mut Vec<SyntaxElement>) {
+        if self.peek() == TokenType::LeftBrace {
+            children.push(SyntaxElement::Node(self.block()));
+        }
+    }
+
+    fn block(&mut self) -> SyntaxNode {
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        while !matches!(self.peek(), TokenType::RightBrace | TokenType::Eof) {
+            self.declaration_or_error(&mut children);
+        }
+        self.eat(TokenType::RightBrace, &mut children);
+        node(SyntaxKind::Block, children)
+    }
+
+    fn expression(&mut self) -> SyntaxNode {
+        if self.depth >= MAX_NESTING {
+            return self.error_node();
+        }
+        self.depth += 1;
+        let expression = self.assignment();
+        self.depth -= 1;
+        expression
+    }
+
+    fn assignment(&mut self) -> SyntaxNode {
+        let target = self.binary(0);
+        if self.peek() != TokenType::Equal {
+            return target;
+        }
+        let mut children = vec![SyntaxElement::Node(target)];
+        self.bump(&mut children);
+        children.push(SyntaxElement::Node(self.expression()));
+        node(SyntaxKind::Assign, children)
+    }
+
+    // Binary operators from loosest to tightest, all left associative.
+    fn binary(&mut self, level: usize) -> SyntaxNode {
+        const LEVELS: &[&[TokenType]] = &[
+            &[TokenType::Or],
+            &[TokenType::And],
+            &[TokenType::BangEqual, TokenType::EqualEqual],
+            &[
+                TokenType::Greater,
+                TokenType::GreaterEqual,
+                TokenType::Less,
+                TokenType::LessEqual,
+            ],
+            &[TokenType::Minus, TokenType::Plus],
+            &[TokenType::Slash, TokenType::Star, TokenType::Percent],
+        ];
+        let Some(operators) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1);
+        while operators.contains(&self.peek()) {
+            let kind = if level < 2 {
+                SyntaxKind::Logical
+            } else {
+                SyntaxKind::Binary
+            };
+            let mut children = vec![SyntaxElement::Node(left)];
+            self.bump(&mut children);
+            children.push(SyntaxElement::Node(self.binary(level + 1)));
+            left = node(kind, children);
+        }
+        left
+    }
+
+    fn unary(&mut self) -> SyntaxNode {
+        if !matches!(self.peek(), TokenType::Bang | TokenType::Minus) {
+            return self.call();
+        }
+        if self.depth >= MAX_NESTING {
+            return self.error_node();
+        }
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        self.depth += 1;
+        children.push(SyntaxElement::Node(self.unary()));
+        self.depth -= 1;
+        node(SyntaxKind::Unary, children)
+    }
+
+    fn call(&mut self) -> SyntaxNode {
+        let mut expression = self.primary();
+        loop {
+            match self.peek() {
+                TokenType::LeftParen => {
+                    let mut args = Vec::new();
+                    self.bump(&mut args);
+                    if self.peek() != TokenType::RightParen {
+                        loop {
+                            args.push(SyntaxElement::Node(self.expression()));
+                            if !self.eat(TokenType::Comma, &mut args) {
+                                break;
+                            }
+                        }
+                    }
+                    self.eat(TokenType::RightParen, &mut args);
+                    let callee = SyntaxElement::Node(expression);
+                    let args = SyntaxElement::Node(node(SyntaxKind::Args, args));
+                    expression = node(SyntaxKind::Call, vec![callee, args]);
+                }
+                TokenType::Dot => {
+                    let mut children = vec![SyntaxElement::Node(expression)];
+                    self.bump(&mut children);
+                    self.eat(TokenType::Identifier, &mut children);
+                    expression = node(SyntaxKind::Get, children);
+                }
+                TokenType::LeftBracket => {
+                    let mut children = vec![SyntaxElement::Node(expression)];
+                    self.bump(&mut children);
+                    children.push(SyntaxElement::Node(self.expression()));
+                    self.eat(TokenType::RightBracket, &mut children);
+                    expression = node(SyntaxKind::Index, children);
+                }
+                _ => return expression,
+            }
+        }
+    }
+
+    fn primary(&mut self) -> SyntaxNode {
+        let kind = match self.peek() {
+            TokenType::Number
+            | TokenType::String
+            | TokenType::True
+            | TokenType::False
+            | TokenType::Nil => SyntaxKind::Literal,
+            TokenType::Identifier => SyntaxKind::Name,
+            TokenType::This => SyntaxKind::This,
+            TokenType::Super => {
+                let mut children = Vec::new();
+                self.bump(&mut children);
+                if self.eat(TokenType::Dot, &mut children) {
+                    self.eat(TokenType::Identifier, &mut children);
+                }
+                return node(SyntaxKind::Super, children);
+            }
+            TokenType::LeftParen => {
+                let mut children = Vec::new();
+                self.bump(&mut children);
+                children.push(SyntaxElement::Node(self.expression()));
+                self.eat(TokenType::RightParen, &mut children);
+                return node(SyntaxKind::Grouping, children);
+            }
+            TokenType::LeftBracket => {
+                let mut children = Vec::new();
+                self.bump(&mut children);
+                if self.peek() != TokenType::RightBracket {
+                    loop {
+                        children.push(SyntaxElement::Node(self.expression()));
+                        if !self.eat(TokenType::Comma, &mut children) {
+                            break;
+                        }
+                    }
+                }
+                self.eat(TokenType::RightBracket, &mut children);
+                return node(SyntaxKind::List, children);
+            }
+            TokenType::LeftBrace => {
+                let mut children = Vec::new();
+                self.bump(&mut children);
+                if self.peek() != TokenType::RightBrace {
+                    loop {
+                        children.push(SyntaxElement::Node(self.expression()));
+                        self.eat(TokenType::Colon, &mut children);
+                        children.push(SyntaxElement::Node(self.expression()));
+                        if !self.eat(TokenType::Comma, &mut children) {
+                            break;
+                        }
+                    }
+                }
+                self.eat(TokenType::RightBrace, &mut children);
+                return node(SyntaxKind::Map, children);
+            }
+            // The segments of an interpolated string, each followed by its expression, then
+            // the rest of the string.
+            TokenType::Interpolation => {
+                let mut children = Vec::new();
+                while self.peek() == TokenType::Interpolation {
+                    self.bump(&mut children);
+                    children.push(SyntaxElement::Node(self.expression()));
+                }
+                self.eat(TokenType::String, &mut children);
+                return node(SyntaxKind::Interpolation, children);
+            }
+            // Leave tokens that end a statement or block to the caller.
+            TokenType::Semicolon | TokenType::RightBrace | TokenType::Eof => {
+                return node(SyntaxKind::Error, Vec::new());
+            }
+            // A keyword that starts a statement is more likely the start of the next line
+            // than a mistake in this expression.
+            TokenType::Class
+            | TokenType::Fun
+            | TokenType::Var
+            | TokenType::For
+            | TokenType::If
+            | TokenType::While
+            | TokenType::Print
+            | TokenType::Return
+            | TokenType::Break
+            | TokenType::Continue
+            | TokenType::Throw
+            | TokenType::Try
+            | TokenType::Import
+            | TokenType::From => return node(SyntaxKind::Error, Vec::new()),
+            _ => return self.error_node(),
+        };
+        let mut children = Vec::new();
+        self.bump(&mut children);
+        node(kind, children)
+    }
+}
+
+fn node(kind: SyntaxKind, children: Vec<SyntaxElement>) -> SyntaxNode {
+    SyntaxNode { kind, children }
+}
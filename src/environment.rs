@@ -0,0 +1,101 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::interpreter::RuntimeError;
+use crate::lexer::Token;
+use crate::value::Value;
+
+pub struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new(enclosing: Option<Rc<RefCell<Environment>>>) -> Rc<RefCell<Environment>> {
+        Rc::new(RefCell::new(Environment {
+            values: HashMap::new(),
+            enclosing,
+        }))
+    }
+
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    /// Looks a name up in this scope only, without walking the enclosing chain.
+    pub fn get_value(&self, name: &str) -> Option<Value> {
+        self.values.get(name).cloned()
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        if let Some(value) = self.values.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => Err(RuntimeError::new(
+                format!("Undefined variable '{}'.", name.lexeme),
+                name.line,
+            )),
+        }
+    }
+
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        if let Some(slot) = self.values.get_mut(&name.lexeme) {
+            *slot = value;
+            return Ok(());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(RuntimeError::new(
+                format!("Undefined variable '{}'.", name.lexeme),
+                name.line,
+            )),
+        }
+    }
+
+    // The resolver guarantees the variable exists at `distance`, so a miss here means the
+    // resolver and interpreter disagree about scopes and is treated as nil.
+    pub fn get_at(env: &Rc<RefCell<Environment>>, distance: usize, name: &str) -> Value {
+        Environment::ancestor(env, distance)
+            .borrow()
+            .values
+            .get(name)
+            .cloned()
+            .unwrap_or(Value::Nil)
+    }
+
+    pub fn assign_at(env: &Rc<RefCell<Environment>>, distance: usize, name: &str, value: Value) {
+        Environment::ancestor(env, distance)
+            .borrow_mut()
+            .values
+            .insert(name.to_string(), value);
+    }
+
+    /// The outermost scope of the chain, which holds the globals of the script or module
+    /// the environment belongs to.
+    pub fn root(env: &Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+        let mut environment = Rc::clone(env);
+        loop {
+            let enclosing = environment.borrow().enclosing.clone();
+            match enclosing {
+                Some(enclosing) => environment = enclosing,
+                None => return environment,
+            }
+        }
+    }
+
+    fn ancestor(env: &Rc<RefCell<Environment>>, distance: usize) -> Rc<RefCell<Environment>> {
+        let mut environment = Rc::clone(env);
+        for _ in 0..distance {
+            let enclosing = environment
+                .borrow()
+                .enclosing
+                .clone()
+                .expect("resolved scope distance is deeper than the environment chain");
+            environment = enclosing;
+        }
+        environment
+    }
+}
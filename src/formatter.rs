@@ -0,0 +1,367 @@
+use crate::lexer::Lexer;
+use crate::lexer::Token;
+use crate::lexer::TokenType;
+use crate::parser::CompileError;
+use crate::parser::Parser;
+
+/// Layout settings for the formatter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Spaces per indentation level.
+    pub indent_width: usize,
+    /// Lines longer than this have their argument lists split, one argument per line.
+    pub line_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent_width: 2,
+            line_width: 80,
+        }
+    }
+}
+
+/// Formats a Lox script into the canonical layout: one statement per line, blocks indented,
+/// single spaces around binary operators and at most one blank line in a row. Comments are
+/// kept where they were, either on a line of their own or trailing a statement.
+///
+/// The formatter works on the token stream rather than the AST, so `for` loops keep their
+/// shape. It refuses to touch source that doesn't parse and returns the parse errors instead.
+pub fn format_source(source: &str, options: &FormatOptions) -> Result<String, Vec<CompileError>> {
+    let mut lexer = Lexer::from_source(source);
+    Parser::new(&mut lexer).parse()?;
+
+    let mut layout = Layout::default();
+    for token in Lexer::from_source(source).with_comments() {
+        layout.push(token);
+    }
+    layout.flush();
+
+    let mut lines = Vec::new();
+    for line in layout.lines {
+        wrap(line, options, &mut lines);
+    }
+    let mut out = String::new();
+    for line in &lines {
+        out.push_str(&render(line, options));
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+struct Piece {
+    token: Token,
+    // A prefix `-` or `!`, printed without a space before its operand.
+    unary: bool,
+    // A brace of a map literal rather than of a block.
+    map: bool,
+}
+
+// One output line at some indentation level. A line without pieces is a blank line.
+struct Line {
+    indent: usize,
+    pieces: Vec<Piece>,
+}
+
+// Splits the token stream into lines and tracks indentation.
+#[derive(Default)]
+struct Layout {
+    lines: Vec<Line>,
+    current: Vec<Piece>,
+    current_indent: usize,
+    depth: usize,
+    parens: usize,
+    // For each open brace, whether it belongs to a map literal.
+    braces: Vec<bool>,
+    // Set after tokens that end a line, like `;` or `{`. The break happens when the next
+    // token arrives so a trailing comment can still join the line.
+    line_break: bool,
+    previous: Option<Token>,
+    // The last token that isn't a comment, to tell a unary minus from a binary one.
+    previous_code: Option<TokenType>,
+    previous_end_line: usize,
+}
+
+impl Layout {
+    fn push(&mut self, token: Token) {
+        let previous_type = self.previous.as_ref().map(|t| t.token_type);
+        let last_type = self.current.last().map(|p| p.token.token_type);
+        let map = match token.token_type {
+            TokenType::LeftBrace => {
+                let map = self.previous_code.is_some_and(expects_operand);
+                self.braces.push(map);
+                map
+            }
+            TokenType::RightBrace => self.braces.pop().unwrap_or(false),
+            _ => false,
+        };
+
+        let joins_line = match token.token_type {
+            TokenType::Comment => self.previous.is_some() && self.previous_end_line == token.line,
+            TokenType::Else | TokenType::Catch | TokenType::Finally => {
+                previous_type == Some(TokenType::RightBrace)
+            }
+            TokenType::RightBrace if !map => last_type == Some(TokenType::LeftBrace),
+            // A block comment only ends the line if the code after it is on a later line.
+            _ => {
+                let after_block_comment = previous_type == Some(TokenType::Comment)
+                    && token.line > self.previous_end_line;
+                !self.line_break && !after_block_comment
+            }
+        };
+        if token.token_type == TokenType::RightBrace && !map {
+            self.depth = self.depth.saturating_sub(1);
+        }
+        if !joins_line {
+            self.flush();
+        }
+
+        if self.current.is_empty() {
+            let blank_line_before = token.line > self.previous_end_line + 1
+                && self.previous.is_some()
+                && previous_type != Some(TokenType::LeftBrace)
+                && token.token_type != TokenType::RightBrace;
+            if blank_line_before {
+                self.lines.push(Line {
+                    indent: 0,
+                    pieces: Vec::new(),
+                });
+            }
+            self.current_indent = self.depth;
+        }
+
+        let unary = match token.token_type {
+            TokenType::Bang => true,
+            TokenType::Minus => !self.previous_code.is_some_and(ends_operand),
+            _ => false,
+        };
+
+        self.line_break = match token.token_type {
+            TokenType::LeftBrace | TokenType::RightBrace if map => false,
+            TokenType::LeftBrace => {
+                self.depth += 1;
+                true
+            }
+            TokenType::LeftParen => {
+                self.parens += 1;
+                false
+            }
+            TokenType::RightParen => {
+                self.parens = self.parens.saturating_sub(1);
+                false
+            }
+            // The semicolons inside a for loop's clauses don't end the line.
+            TokenType::Semicolon => self.parens == 0,
+            TokenType::RightBrace => true,
+            TokenType::Comment => token.lexeme.starts_with("//"),
+            _ => false,
+        };
+        if token.token_type != TokenType::Comment {
+            self.previous_code = Some(token.token_type);
+        }
+        self.previous_end_line = token.line + token.lexeme.matches('\n').count();
+        self.previous = Some(token.clone());
+        self.current.push(Piece { token, unary, map });
+    }
+
+    fn flush(&mut self) {
+        if self.current.is_empty() {
+            return;
+        }
+        self.lines.push(Line {
+            indent: self.current_indent,
+            pieces: std::mem::take(&mut self.current),
+        });
+    }
+}
+
+// Tokens after which a `-` is a binary minus.
+fn ends_operand(token_type: TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::Identifier
+            | TokenType::Number
+            | TokenType::String
+            | TokenType::RightParen
+            | TokenType::RightBracket
+            | TokenType::True
+            | TokenType::False
+            | TokenType::Nil
+            | TokenType::This
+    )
+}
+
+// Tokens after which a `{` starts a map literal rather than a block.
+fn expects_operand(token_type: TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::LeftParen
+            | TokenType::LeftBracket
+            | TokenType::Comma
+            | TokenType::Colon
+            | TokenType::Minus
+            | TokenType::Plus
+            | TokenType::Slash
+            | TokenType::Star
+            | TokenType::Percent
+            | TokenType::Bang
+            | TokenType::BangEqual
+            | TokenType::Equal
+            | TokenType::EqualEqual
+            | TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual
+            | TokenType::Interpolation
+            | TokenType::And
+            | TokenType::Or
+            | TokenType::In
+            | TokenType::Print
+            | TokenType::Return
+            | TokenType::Throw
+    )
+}
+
+fn space_between(left: &Piece, right: &Piece) -> bool {
+    if left.unary {
+        return false;
+    }
+    match (left.token.token_type, right.token.token_type) {
+        (_, TokenType::Comment) => true,
+        (
+            _,
+            TokenType::RightParen
+            | TokenType::RightBracket
+            | TokenType::Comma
+            | TokenType::Colon
+            | TokenType::Semicolon
+            | TokenType::Dot,
+        ) => false,
+        (TokenType::LeftBrace, _) if left.map => false,
+        (_, TokenType::RightBrace) if right.map => false,
+        (
+            TokenType::LeftParen
+            | TokenType::LeftBracket
+            | TokenType::Dot
+            | TokenType::Interpolation,
+            _,
+        ) => false,
+        // The end of an interpolated expression, `}` up to the next `${` or the closing quote.
+        (_, TokenType::Interpolation | TokenType::String)
+            if right.token.lexeme.starts_with('}') =>
+        {
+            false
+        }
+        (TokenType::LeftBrace, TokenType::RightBrace) => false,
+        // Calls, but not `if (` or `while (`.
+        (
+            TokenType::Identifier | TokenType::RightParen | TokenType::RightBracket,
+            TokenType::LeftParen,
+        ) => false,
+        // Indexing, but not a list literal after an operator or keyword.
+        (left, TokenType::LeftBracket) if ends_operand(left) => false,
+        _ => true,
+    }
+}
+
+fn render(line: &Line, options: &FormatOptions) -> String {
+    let mut out = String::new();
+    if line.pieces.is_empty() {
+        return out;
+    }
+    out.push_str(&" ".repeat(line.indent * options.indent_width));
+    for (i, piece) in line.pieces.iter().enumerate() {
+        if i > 0 && space_between(&line.pieces[i - 1], piece) {
+            out.push(' ');
+        }
+        out.push_str(&piece.token.lexeme);
+    }
+    out
+}
+
+// Splits a line that is too long at its first parenthesized list, putting each element on a
+// line of its own one level deeper, and then wraps those lines in turn.
+fn wrap(line: Line, options: &FormatOptions, out: &mut Vec<Line>) {
+    if render(&line, options).chars().count() <= options.line_width {
+        out.push(line);
+        return;
+    }
+    let Some((open, commas, close)) = first_list(&line.pieces) else {
+        out.push(line);
+        return;
+    };
+
+    let indent = line.indent;
+    let mut head = Vec::new();
+    let mut element = Vec::new();
+    let mut elements = Vec::new();
+    let mut tail = Vec::new();
+    for (i, piece) in line.pieces.into_iter().enumerate() {
+        if i <= open {
+            head.push(piece);
+        } else if i < close {
+            element.push(piece);
+            if commas.contains(&i) {
+                elements.push(std::mem::take(&mut element));
+            }
+        } else {
+            tail.push(piece);
+        }
+    }
+    elements.push(element);
+
+    out.push(Line {
+        indent,
+        pieces: head,
+    });
+    for element in elements {
+        wrap(
+            Line {
+                indent: indent + 1,
+                pieces: element,
+            },
+            options,
+            out,
+        );
+    }
+    wrap(
+        Line {
+            indent,
+            pieces: tail,
+        },
+        options,
+        out,
+    );
+}
+
+// Finds the first top-level parenthesized group that contains commas, returning the positions
+// of its parentheses and of its top-level commas.
+fn first_list(pieces: &[Piece]) -> Option<(usize, Vec<usize>, usize)> {
+    let mut depth = 0;
+    let mut open = 0;
+    let mut commas = Vec::new();
+    for (i, piece) in pieces.iter().enumerate() {
+        match piece.token.token_type {
+            TokenType::LeftParen => {
+                if depth == 0 {
+                    open = i;
+                    commas.clear();
+                }
+                depth += 1;
+            }
+            TokenType::RightParen => {
+                if depth == 0 {
+                    return None;
+                }
+                depth -= 1;
+                if depth == 0 && !commas.is_empty() {
+                    return Some((open, commas, i));
+                }
+            }
+            TokenType::Comma if depth == 1 => commas.push(i),
+            _ => {}
+        }
+    }
+    None
+}
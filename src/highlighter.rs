@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+
+use crate::cst;
+use crate::cst::SyntaxToken;
+use crate::cst::TriviaKind;
+use crate::lexer::Lexer;
+use crate::lexer::TokenType;
+use crate::parser::Parser;
+use crate::symbols::DeclarationKind;
+use crate::symbols::SymbolIndex;
+
+/// What a piece of source is, for choosing its color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Highlight {
+    Keyword,
+    Class,
+    Function,
+    Method,
+    Parameter,
+    Variable,
+    Property,
+    Number,
+    String,
+    Comment,
+    Operator,
+    Punctuation,
+    Error,
+}
+
+impl Highlight {
+    /// The CSS class used in HTML output.
+    pub fn name(self) -> &'static str {
+        match self {
+            Highlight::Keyword => "keyword",
+            Highlight::Class => "class",
+            Highlight::Function => "function",
+            Highlight::Method => "method",
+            Highlight::Parameter => "parameter",
+            Highlight::Variable => "variable",
+            Highlight::Property => "property",
+            Highlight::Number => "number",
+            Highlight::String => "string",
+            Highlight::Comment => "comment",
+            Highlight::Operator => "operator",
+            Highlight::Punctuation => "punctuation",
+            Highlight::Error => "error",
+        }
+    }
+
+    // SGR parameters for terminal output, `None` for the terminal's default color.
+    fn ansi(self) -> Option<&'static str> {
+        match self {
+            Highlight::Keyword => Some("35"),
+            Highlight::Class => Some("1;33"),
+            Highlight::Function | Highlight::Method => Some("34"),
+            Highlight::Parameter => Some("3"),
+            Highlight::Property => Some("36"),
+            Highlight::Number => Some("33"),
+            Highlight::String => Some("32"),
+            Highlight::Comment => Some("90"),
+            Highlight::Error => Some("4;31"),
+            Highlight::Variable | Highlight::Operator | Highlight::Punctuation => None,
+        }
+    }
+}
+
+/// Splits source into consecutive spans that add up to the whole text, each with its
+/// highlight. Whitespace has none.
+///
+/// Identifiers are classified by what they name when the source parses, so a parameter
+/// stays a parameter wherever it's used. Otherwise, and for names that are never declared
+/// like the natives, the surrounding tokens decide.
+pub fn highlight(source: &str) -> Vec<(Option<Highlight>, String)> {
+    let names = declared_names(source);
+    let tokens = cst::tokenize(source);
+
+    let mut spans = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        for trivia in &token.leading {
+            spans.push((trivia_highlight(trivia.kind), trivia.text.clone()));
+        }
+        if !token.text.is_empty() {
+            let highlight = match token.kind {
+                TokenType::Identifier => {
+                    let declared = names.get(&(token.line, token.column)).copied();
+                    declared.unwrap_or_else(|| guess_identifier(&tokens, i))
+                }
+                kind => token_highlight(kind),
+            };
+            spans.push((Some(highlight), token.text.clone()));
+        }
+        for trivia in &token.trailing {
+            spans.push((trivia_highlight(trivia.kind), trivia.text.clone()));
+        }
+    }
+    spans
+}
+
+/// Renders source with ANSI color escapes for a terminal.
+pub fn to_ansi(source: &str) -> String {
+    let mut out = String::new();
+    for (highlight, text) in highlight(source) {
+        match highlight.and_then(Highlight::ansi) {
+            Some(code) => out.push_str(&format!("\x1b[{}m{}\x1b[0m", code, text)),
+            None => out.push_str(&text),
+        }
+    }
+    out
+}
+
+/// Renders source as a standalone HTML page, with a stylesheet for the `Highlight` classes.
+pub fn to_html(source: &str, title: &str) -> String {
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>{}</title>\n", escape_html(title)));
+    out.push_str(STYLESHEET);
+    out.push_str("</head>\n<body>\n<pre class=\"rox\"><code>");
+    for (highlight, text) in highlight(source) {
+        match highlight {
+            Some(highlight) => out.push_str(&format!(
+                "<span class=\"{}\">{}</span>",
+                highlight.name(),
+                escape_html(&text)
+            )),
+            None => out.push_str(&escape_html(&text)),
+        }
+    }
+    out.push_str("</code></pre>\n</body>\n</html>\n");
+    out
+}
+
+const STYLESHEET: &str = "<style>
+pre.rox { background: #fafafa; color: #383a42; padding: 1em; }
+.rox .keyword { color: #a626a4; }
+.rox .class { color: #c18401; font-weight: bold; }
+.rox .function, .rox .method { color: #4078f2; }
+.rox .parameter { font-style: italic; }
+.rox .property { color: #0184bc; }
+.rox .number { color: #986801; }
+.rox .string { color: #50a14f; }
+.rox .comment { color: #a0a1a7; font-style: italic; }
+.rox .error { color: #e45649; text-decoration: underline wavy; }
+</style>
+";
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Where each declared name and each use of one is, and what it names.
+fn declared_names(source: &str) -> HashMap<(usize, usize), Highlight> {
+    let mut lexer = Lexer::from_source(source);
+    let Ok(statements) = Parser::new(&mut lexer).parse() else {
+        return HashMap::new();
+    };
+    let index = SymbolIndex::build(&statements);
+
+    let mut names = HashMap::new();
+    for declaration in &index.declarations {
+        let highlight = match declaration.kind {
+            DeclarationKind::Variable => Highlight::Variable,
+            DeclarationKind::Parameter => Highlight::Parameter,
+            DeclarationKind::Function => Highlight::Function,
+            DeclarationKind::Class => Highlight::Class,
+            DeclarationKind::Method => Highlight::Method,
+        };
+        names.insert((declaration.name.line, declaration.name.column), highlight);
+    }
+    for (token, declaration) in &index.references {
+        let name = &index.declarations[*declaration].name;
+        if let Some(&highlight) = names.get(&(name.line, name.column)) {
+            names.insert((token.line, token.column), highlight);
+        }
+    }
+    names
+}
+
+// Classifies an identifier from the tokens around it.
+fn guess_identifier(tokens: &[SyntaxToken], i: usize) -> Highlight {
+    let previous = i.checked_sub(1).map(|i| tokens[i].kind);
+    let next = tokens.get(i + 1).map(|t| t.kind);
+    let superclass =
+        previous == Some(TokenType::Less) && i >= 3 && tokens[i - 3].kind == TokenType::Class;
+    if superclass {
+        return Highlight::Class;
+    }
+    // The `as` of an import, which the lexer leaves as an identifier.
+    if tokens[i].text == "as" && previous == Some(TokenType::String) {
+        return Highlight::Keyword;
+    }
+    match (previous, next) {
+        (Some(TokenType::Dot), Some(TokenType::LeftParen)) => Highlight::Method,
+        (Some(TokenType::Dot), _) => Highlight::Property,
+        (Some(TokenType::Class), _) => Highlight::Class,
+        (Some(TokenType::Fun), _) | (_, Some(TokenType::LeftParen)) => Highlight::Function,
+        _ => Highlight::Variable,
+    }
+}
+
+fn token_highlight(kind: TokenType) -> Highlight {
+    match kind {
+        TokenType::And
+        | TokenType::Break
+        | TokenType::Catch
+        | TokenType::Class
+        | TokenType::Continue
+        | TokenType::Else
+        | TokenType::False
+        | TokenType::Finally
+        | TokenType::From
+        | TokenType::Fun
+        | TokenType::For
+        | TokenType::If
+        | TokenType::Import
+        | TokenType::In
+        | TokenType::Nil
+        | TokenType::Or
+        | TokenType::Print
+        | TokenType::Return
+        | TokenType::Super
+        | TokenType::This
+        | TokenType::Throw
+        | TokenType::True
+        | TokenType::Try
+        | TokenType::Var
+        | TokenType::While => Highlight::Keyword,
+        TokenType::Number => Highlight::Number,
+        TokenType::String | TokenType::Interpolation => Highlight::String,
+        TokenType::Comment => Highlight::Comment,
+        TokenType::Minus
+        | TokenType::Plus
+        | TokenType::Slash
+        | TokenType::Star
+        | TokenType::Percent
+        | TokenType::Bang
+        | TokenType::BangEqual
+        | TokenType::Equal
+        | TokenType::EqualEqual
+        | TokenType::Greater
+        | TokenType::GreaterEqual
+        | TokenType::Less
+        | TokenType::LessEqual => Highlight::Operator,
+        TokenType::LeftParen
+        | TokenType::RightParen
+        | TokenType::LeftBrace
+        | TokenType::RightBrace
+        | TokenType::LeftBracket
+        | TokenType::RightBracket
+        | TokenType::Comma
+        | TokenType::Colon
+        | TokenType::Dot
+        | TokenType::Semicolon => Highlight::Punctuation,
+        TokenType::Identifier => Highlight::Variable,
+        TokenType::Error | TokenType::Whitespace | TokenType::Newline | TokenType::Eof => {
+            Highlight::Error
+        }
+    }
+}
+
+fn trivia_highlight(kind: TriviaKind) -> Option<Highlight> {
+    match kind {
+        TriviaKind::Comment => Some(Highlight::Comment),
+        TriviaKind::Whitespace | TriviaKind::Newline => None,
+    }
+}
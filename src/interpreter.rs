@@ -0,0 +1,1561 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Formatter;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::environment::Environment;
+use crate::lexer::Lexer;
+use crate::lexer::RoxError;
+use crate::lexer::Token;
+use crate::lexer::TokenType;
+use crate::natives;
+use crate::natives::Random;
+use crate::optimizer;
+use crate::parser::Expr;
+use crate::parser::FunctionDecl;
+use crate::parser::LiteralValue;
+use crate::parser::Parser;
+use crate::parser::Stmt;
+use crate::resolver::Resolver;
+use crate::value::LoxClass;
+use crate::value::LoxError;
+use crate::value::LoxFunction;
+use crate::value::LoxInstance;
+use crate::value::LoxMap;
+use crate::value::LoxModule;
+use crate::value::LoxRange;
+use crate::value::NativeFunction;
+use crate::value::Value;
+
+/// Distinguishes ordinary script errors from the ones raised when a configured limit is hit,
+/// so hosts can tell a buggy script from a runaway one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeErrorKind {
+    Error,
+    StackOverflow,
+    StepLimit,
+    AllocationLimit,
+    Interrupted,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    pub message: String,
+    pub line: usize,
+    /// The calls that led to the error, innermost first, when they are known.
+    pub trace: Vec<StackFrame>,
+}
+
+impl RuntimeError {
+    pub fn new(message: String, line: usize) -> RuntimeError {
+        RuntimeError::with_kind(RuntimeErrorKind::Error, message, line)
+    }
+
+    pub fn with_kind(kind: RuntimeErrorKind, message: String, line: usize) -> RuntimeError {
+        RuntimeError {
+            kind,
+            message,
+            line,
+            trace: Vec::new(),
+        }
+    }
+}
+
+/// One function call on the way to an error: the function, or None for the top level of
+/// the script, and the line it had reached.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    pub function: Option<String>,
+    pub line: usize,
+}
+
+impl std::fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match &self.function {
+            Some(function) => write!(f, "[line {}] in {}()", self.line, function),
+            None => write!(f, "[line {}] in script", self.line),
+        }
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        if self.trace.is_empty() {
+            return write!(f, "{}\n[line {}]", self.message, self.line);
+        }
+        write!(f, "{}", self.message)?;
+        // A runaway recursion would fill the screen with the same frame, so runs of one
+        // frame are shown once with a count.
+        let mut frames = self.trace.iter().peekable();
+        while let Some(frame) = frames.next() {
+            write!(f, "\n{}", frame)?;
+            let mut repeats = 0;
+            while frames.next_if_eq(&frame).is_some() {
+                repeats += 1;
+            }
+            if repeats > 0 {
+                write!(f, "\n[previous frame repeated {} more times]", repeats)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Anything that stops normal statement-by-statement execution and travels up the Rust stack
+/// until something handles it.
+pub enum Unwind {
+    Error(RuntimeError),
+    /// Raised by `throw`. Runtime errors become the same kind of value when caught.
+    Throw(Rc<LoxError>),
+    Return(Value),
+    Break,
+    Continue,
+    Exit(i32),
+}
+
+impl Unwind {
+    /// Shorthand for natives, which do not know the line they were called from.
+    pub fn error(message: &str) -> Unwind {
+        Unwind::Error(RuntimeError::new(message.to_string(), 0))
+    }
+
+    fn at_line(self, line: usize) -> Unwind {
+        match self {
+            Unwind::Error(mut error) if error.line == 0 => {
+                error.line = line;
+                Unwind::Error(error)
+            }
+            other => other,
+        }
+    }
+}
+
+impl From<Unwind> for RoxError {
+    fn from(unwind: Unwind) -> RoxError {
+        match unwind {
+            Unwind::Error(error) => RoxError::RuntimeError(error),
+            Unwind::Throw(error) => RoxError::RuntimeError(RuntimeError {
+                kind: RuntimeErrorKind::Error,
+                message: error.message.clone(),
+                line: error.line,
+                trace: error.trace.clone(),
+            }),
+            Unwind::Exit(code) => RoxError::Exit(code),
+            // The resolver rejects top-level returns, only a misbehaving native can get here.
+            Unwind::Return(_) => RoxError::RuntimeError(RuntimeError::new(
+                "Can't return from top-level code.".to_string(),
+                0,
+            )),
+            // Likewise for jumps outside of a loop.
+            Unwind::Break | Unwind::Continue => RoxError::RuntimeError(RuntimeError::new(
+                "Can't jump outside of a loop.".to_string(),
+                0,
+            )),
+        }
+    }
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Unwind {
+        Unwind::Error(error)
+    }
+}
+
+/// An in-memory sink for script output. Clones share the same buffer, so one can be handed to
+/// the interpreter and the other kept to read what was printed.
+#[derive(Clone, Default)]
+pub struct CapturedOutput {
+    buffer: Rc<RefCell<Vec<u8>>>,
+}
+
+impl CapturedOutput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns everything written so far and clears the buffer.
+    pub fn take_string(&self) -> String {
+        let bytes = std::mem::take(&mut *self.buffer.borrow_mut());
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+impl Write for CapturedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+/// Stack the interpreter may use by default, half of the 2 MiB Rust gives spawned threads.
+pub const DEFAULT_MAX_STACK_BYTES: usize = 1024 * 1024;
+
+/// Stack size for threads that run the interpreter, see `Limits::for_stack_size`.
+pub const INTERPRETER_STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Resource limits for running untrusted scripts. Hitting one, or being interrupted, ends
+/// the run with an error of its own `RuntimeErrorKind` for the host to handle. Scripts can't
+/// catch these errors, so they can't keep going past a limit; `finally` blocks still run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// Expressions a single `eval` or `call` may evaluate. Unlimited when None.
+    pub max_steps: Option<u64>,
+    /// Nested calls allowed before a "Stack overflow." error.
+    pub max_call_depth: usize,
+    /// Native stack an `eval` or `call` may use before a "Stack overflow." error, whatever
+    /// the call depth. Must stay below the stack of the thread running the interpreter.
+    pub max_stack_bytes: usize,
+    /// Bytes a single `eval` or `call` may allocate in total, counting strings, instances,
+    /// fields and environments. Freed memory is not credited back, so this budgets the work a
+    /// run does rather than capping its live heap. Unlimited when None.
+    pub max_allocated_bytes: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_steps: None,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_stack_bytes: DEFAULT_MAX_STACK_BYTES,
+            max_allocated_bytes: None,
+        }
+    }
+}
+
+impl Limits {
+    /// Default limits for a thread with `stack_size` bytes of stack, keeping half of it
+    /// spare for the frames between checks and for natives.
+    pub fn for_stack_size(stack_size: usize) -> Self {
+        Limits {
+            max_stack_bytes: stack_size / 2,
+            ..Limits::default()
+        }
+    }
+}
+
+/// What a script may reach outside the interpreter. Everything is off by default, so
+/// untrusted scripts stay sandboxed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Permissions {
+    /// `readFile`, `writeFile` and `listDir`.
+    pub fs: bool,
+    /// `getenv` and `args`.
+    pub env: bool,
+}
+
+/// Stops a running script from another thread. The interpreter checks it at every loop
+/// iteration and call, so a native blocked in Rust code is not interrupted until it returns.
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle {
+    flag: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops the `eval` or `call` that is running, or the next one if none is. The request
+    /// is dropped once that run ends, so the interpreter can be reused.
+    pub fn interrupt(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    fn is_interrupted(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    fn clear(&self) {
+        self.flag.store(false, Ordering::Relaxed);
+    }
+}
+
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    // Scope distances for the code being run, swapped in and out with functions.
+    locals: Rc<HashMap<usize, usize>>,
+    output: Box<dyn Write>,
+    limits: Limits,
+    steps: u64,
+    call_depth: usize,
+    // Stack address where the outermost `eval` or `call` started.
+    stack_base: Option<usize>,
+    // The functions being run, outermost first, with the line each was called from.
+    calls: Vec<(Rc<FunctionDecl>, usize)>,
+    allocated_bytes: usize,
+    // The script and the modules being run, outermost first.
+    importing: Vec<PathBuf>,
+    // The file each set of top-level globals was loaded from. Imports are relative to the
+    // file whose globals are current, or to the working directory when there is none.
+    files: Vec<(Rc<RefCell<Environment>>, PathBuf)>,
+    // Every module loaded so far, by canonical path.
+    modules: HashMap<PathBuf, Rc<LoxModule>>,
+    random: Random,
+    permissions: Permissions,
+    // What `args()` returns.
+    args: Vec<String>,
+    interrupt: InterruptHandle,
+    optimize: bool,
+    // Line of the last evaluated expression that had one, for errors not tied to a token.
+    line: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        let globals = Environment::new(None);
+        let mut interpreter = Self {
+            environment: Rc::clone(&globals),
+            globals,
+            locals: Rc::default(),
+            output: Box::new(io::stdout()),
+            limits: Limits::default(),
+            steps: 0,
+            call_depth: 0,
+            stack_base: None,
+            calls: Vec::new(),
+            allocated_bytes: 0,
+            importing: Vec::new(),
+            files: Vec::new(),
+            modules: HashMap::new(),
+            random: Random::from_clock(),
+            permissions: Permissions::default(),
+            args: Vec::new(),
+            interrupt: InterruptHandle::new(),
+            optimize: false,
+            line: 0,
+        };
+        natives::define_globals(&mut interpreter);
+        interpreter
+    }
+
+    /// Redirects `print` and other script output, which goes to stdout by default.
+    pub fn set_output<W: Write + 'static>(&mut self, output: W) {
+        self.output = Box::new(output);
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn set_permissions(&mut self, permissions: Permissions) {
+        self.permissions = permissions;
+    }
+
+    pub fn permissions(&self) -> Permissions {
+        self.permissions
+    }
+
+    /// Sets the command line arguments scripts see through `args()`.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Runs the optimizer over every script before it runs. Off by default.
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    /// Names the file the scripts passed to `eval` come from, so their imports are found
+    /// next to it and an import of the script itself is reported as a cycle.
+    pub fn set_script_path(&mut self, path: &Path) {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.files = vec![(Rc::clone(&self.globals), path.clone())];
+        self.importing = vec![path];
+    }
+
+    /// The generator behind `math.random()`, shared by the script and its modules.
+    pub fn random(&mut self) -> &mut Random {
+        &mut self.random
+    }
+
+    /// Returns a handle that interrupts this interpreter when triggered.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    /// Makes this interpreter listen to an existing handle, for hosts that create the handle
+    /// before the interpreter.
+    pub fn set_interrupt_handle(&mut self, handle: InterruptHandle) {
+        self.interrupt = handle;
+    }
+
+    fn check_interrupt(&self, line: usize) -> Result<(), Unwind> {
+        if self.interrupt.is_interrupted() {
+            return Err(RuntimeError::with_kind(
+                RuntimeErrorKind::Interrupted,
+                "Interrupted.".to_string(),
+                line,
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Charges `bytes` against the allocation limit.
+    pub fn allocate(&mut self, bytes: usize) -> Result<(), Unwind> {
+        self.allocated_bytes += bytes;
+        match self.limits.max_allocated_bytes {
+            Some(max) if self.allocated_bytes > max => Err(RuntimeError::with_kind(
+                RuntimeErrorKind::AllocationLimit,
+                "Allocation limit exceeded.".to_string(),
+                self.line,
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_stack(&self, line: usize) -> Result<(), Unwind> {
+        match self.stack_base {
+            Some(base) if base.abs_diff(stack_address()) > self.limits.max_stack_bytes => {
+                Err(RuntimeError::with_kind(
+                    RuntimeErrorKind::StackOverflow,
+                    "Stack overflow.".to_string(),
+                    line,
+                )
+                .into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Every evaluated expression costs one step.
+    fn tick(&mut self, expr: &Expr) -> Result<(), Unwind> {
+        if let Some(line) = expr.line() {
+            self.line = line;
+        }
+        self.check_stack(self.line)?;
+        self.steps += 1;
+        match self.limits.max_steps {
+            Some(max) if self.steps > max => Err(RuntimeError::with_kind(
+                RuntimeErrorKind::StepLimit,
+                "Step limit exceeded.".to_string(),
+                self.line,
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes a line of script output.
+    pub fn write_line(&mut self, text: &str) -> Result<(), Unwind> {
+        writeln!(self.output, "{}", text).map_err(|e| {
+            RuntimeError::new(format!("Could not write output: {}.", e), self.line).into()
+        })
+    }
+
+    /// Lexes, parses, resolves and runs `source` against the current globals. If the last
+    /// statement is an expression statement its value is returned, otherwise nil.
+    pub fn eval(&mut self, source: &str) -> Result<Value, RoxError> {
+        let outermost = self.enter();
+        let result = self.eval_source(source);
+        self.leave(outermost);
+        result
+    }
+
+    // Starts an `eval` or `call`. Returns false when a native started it while a script was
+    // already running, which then shares the outer one's budgets.
+    fn enter(&mut self) -> bool {
+        if self.stack_base.is_some() {
+            return false;
+        }
+        self.stack_base = Some(stack_address());
+        self.steps = 0;
+        self.allocated_bytes = 0;
+        true
+    }
+
+    fn leave(&mut self, outermost: bool) {
+        if outermost {
+            self.stack_base = None;
+            // The interrupt was meant for the run that just ended, whether or not it noticed.
+            self.interrupt.clear();
+        }
+    }
+
+    fn eval_source(&mut self, source: &str) -> Result<Value, RoxError> {
+        let mut lexer = Lexer::from_source(source);
+        let statements = Parser::new(&mut lexer)
+            .parse()
+            .map_err(RoxError::CompileError)?;
+        let locals = Resolver::new()
+            .resolve(&statements)
+            .map_err(RoxError::CompileError)?;
+        let previous_locals = std::mem::replace(&mut self.locals, Rc::new(locals));
+        let statements = if self.optimize {
+            optimizer::optimize(&statements)
+        } else {
+            statements
+        };
+
+        let result = match statements.split_last() {
+            Some((Stmt::Expression { expr }, rest)) => {
+                self.interpret(rest).and_then(|()| self.evaluate(expr))
+            }
+            _ => self.interpret(&statements).map(|()| Value::Nil),
+        };
+        self.locals = previous_locals;
+        result.map_err(|unwind| self.traced(unwind).into())
+    }
+
+    /// Calls the global function `name` with `args`.
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, RoxError> {
+        let Some(callee) = self.get_global(name) else {
+            return Err(RoxError::RuntimeError(RuntimeError::new(
+                format!("Undefined variable '{}'.", name),
+                0,
+            )));
+        };
+        let outermost = self.enter();
+        let result = self.call_value(callee, args, 0);
+        self.leave(outermost);
+        result.map_err(|unwind| {
+            let mut error = RoxError::from(unwind);
+            // The outermost call came from the host rather than from a script.
+            if let RoxError::RuntimeError(error) = &mut error
+                && error
+                    .trace
+                    .last()
+                    .is_some_and(|frame| frame.function.is_none())
+            {
+                error.trace.pop();
+            }
+            error
+        })
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.borrow().get_value(name)
+    }
+
+    pub fn set_global<V: Into<Value>>(&mut self, name: &str, value: V) {
+        self.globals.borrow_mut().define(name, value.into());
+    }
+
+    /// Registers a native function as a global.
+    pub fn define_native(&mut self, native: NativeFunction) {
+        let name = native.name.clone();
+        self.globals
+            .borrow_mut()
+            .define(&name, Value::Native(Rc::new(native)));
+    }
+
+    /// Sets the scope distances found by the resolver for the statements run next. Functions
+    /// keep the distances they were declared with.
+    pub fn resolve(&mut self, locals: HashMap<usize, usize>) {
+        self.locals = Rc::new(locals);
+    }
+
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), Unwind> {
+        for statement in statements {
+            self.execute(statement)?;
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+        match stmt {
+            Stmt::Expression { expr } => {
+                self.evaluate(expr)?;
+            }
+            Stmt::Print { expr } => {
+                let value = self.evaluate(expr)?;
+                self.write_line(&value.to_string())?;
+            }
+            Stmt::Var { name, initializer } => {
+                let value = match initializer {
+                    Some(initializer) => self.evaluate(initializer)?,
+                    None => Value::Nil,
+                };
+                self.allocate(size_of::<Value>() + name.lexeme.len())?;
+                self.environment.borrow_mut().define(&name.lexeme, value);
+            }
+            Stmt::Block { statements } => self.execute_scoped(statements)?,
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                // Walk an `else if` chain in a loop, long chains would use up the stack.
+                let (mut condition, mut then_branch, mut else_branch) =
+                    (condition, then_branch, else_branch);
+                loop {
+                    if self.evaluate(condition)?.is_truthy() {
+                        self.execute(then_branch)?;
+                        break;
+                    }
+                    match else_branch.as_deref() {
+                        Some(Stmt::If {
+                            condition: next_condition,
+                            then_branch: next_then,
+                            else_branch: next_else,
+                        }) => {
+                            condition = next_condition;
+                            then_branch = next_then;
+                            else_branch = next_else;
+                        }
+                        Some(statement) => {
+                            self.execute(statement)?;
+                            break;
+                        }
+                        None => break,
+                    }
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => {
+                while self.evaluate(condition)?.is_truthy() {
+                    if !loop_body(self.execute(body))? {
+                        break;
+                    }
+                    if let Some(increment) = increment {
+                        self.evaluate(increment)?;
+                    }
+                    self.check_interrupt(self.line)?;
+                }
+            }
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => {
+                let value = self.evaluate(iterable)?;
+                let mut iteration = self
+                    .iteration(value, name.line)
+                    .map_err(|error| error.at_line(name.line))?;
+                while let Some(item) = self.next_item(&mut iteration, name.line)? {
+                    // Fetching the next item costs a step, so an empty body still runs out.
+                    self.tick(iterable)?;
+                    // Each pass gets its own variable, so closures made in the body keep the
+                    // item they saw.
+                    self.allocate(size_of::<Environment>())?;
+                    let environment = Environment::new(Some(Rc::clone(&self.environment)));
+                    environment.borrow_mut().define(&name.lexeme, item);
+                    let result = self.execute_block(std::slice::from_ref(body), environment);
+                    if !loop_body(result)? {
+                        break;
+                    }
+                    self.check_interrupt(self.line)?;
+                }
+            }
+            Stmt::Function { decl } => {
+                self.allocate(size_of::<LoxFunction>())?;
+                let function = LoxFunction {
+                    declaration: Rc::clone(decl),
+                    closure: Rc::clone(&self.environment),
+                    locals: Rc::clone(&self.locals),
+                    is_initializer: false,
+                };
+                self.environment
+                    .borrow_mut()
+                    .define(&decl.name.lexeme, Value::Function(Rc::new(function)));
+            }
+            Stmt::Throw { keyword, value } => {
+                let error = match self.evaluate(value)? {
+                    // Rethrowing a caught error keeps where it first came from.
+                    Value::Error(error) => error,
+                    value => Rc::new(LoxError {
+                        message: value.to_string(),
+                        line: keyword.line,
+                        value,
+                        trace: self.stack_trace(keyword.line),
+                    }),
+                };
+                return Err(Unwind::Throw(error));
+            }
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                let mut result = self.execute_scoped(body);
+                if let Some((name, handler)) = catch
+                    && let Err(unwind) = result
+                {
+                    result = match caught(self.traced(unwind)) {
+                        Ok(error) => {
+                            self.allocate(size_of::<Environment>())?;
+                            let environment = Environment::new(Some(Rc::clone(&self.environment)));
+                            environment
+                                .borrow_mut()
+                                .define(&name.lexeme, Value::Error(error));
+                            self.execute_block(handler, environment)
+                        }
+                        Err(unwind) => Err(unwind),
+                    };
+                }
+                let hit_limit = matches!(
+                    &result,
+                    Err(Unwind::Error(error)) if error.kind != RuntimeErrorKind::Error
+                );
+                if let Some(finally) = finally {
+                    let finished = self.execute_scoped(finally);
+                    // Whatever ends the finally block early replaces what the rest was doing,
+                    // except a limit, which always ends the run.
+                    if !hit_limit {
+                        finished?;
+                    }
+                }
+                result?;
+            }
+            Stmt::Import {
+                path, alias, names, ..
+            } => {
+                let module = self.import(path)?;
+                if let Some(alias) = alias {
+                    self.allocate(size_of::<Value>() + alias.lexeme.len())?;
+                    self.environment
+                        .borrow_mut()
+                        .define(&alias.lexeme, Value::Module(Rc::clone(&module)));
+                }
+                for name in names {
+                    let Some(value) = module.get(&name.lexeme) else {
+                        return Err(operand_error(name, &no_export(&module, &name.lexeme)));
+                    };
+                    self.allocate(size_of::<Value>() + name.lexeme.len())?;
+                    self.environment.borrow_mut().define(&name.lexeme, value);
+                }
+            }
+            Stmt::Break { .. } => return Err(Unwind::Break),
+            Stmt::Continue { .. } => return Err(Unwind::Continue),
+            Stmt::Return { value, .. } => {
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::Nil,
+                };
+                return Err(Unwind::Return(value));
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let superclass = match superclass {
+                    Some(expr) => match self.evaluate(expr)? {
+                        Value::Class(class) => Some(class),
+                        _ => {
+                            let line = match expr {
+                                Expr::Variable { name, .. } => name.line,
+                                _ => name.line,
+                            };
+                            return Err(RuntimeError::new(
+                                "Superclass must be a class.".to_string(),
+                                line,
+                            )
+                            .into());
+                        }
+                    },
+                    None => None,
+                };
+
+                self.allocate(size_of::<LoxClass>())?;
+                self.environment
+                    .borrow_mut()
+                    .define(&name.lexeme, Value::Nil);
+
+                let enclosing = Rc::clone(&self.environment);
+                if let Some(superclass) = &superclass {
+                    self.environment = Environment::new(Some(Rc::clone(&self.environment)));
+                    self.environment
+                        .borrow_mut()
+                        .define("super", Value::Class(Rc::clone(superclass)));
+                }
+
+                let mut class_methods = HashMap::new();
+                for method in methods {
+                    let function = LoxFunction {
+                        declaration: Rc::clone(method),
+                        closure: Rc::clone(&self.environment),
+                        locals: Rc::clone(&self.locals),
+                        is_initializer: method.name.lexeme == "init",
+                    };
+                    class_methods.insert(method.name.lexeme.clone(), Rc::new(function));
+                }
+
+                let class = LoxClass {
+                    name: name.lexeme.clone(),
+                    superclass,
+                    methods: class_methods,
+                };
+
+                self.environment = enclosing;
+                self.environment
+                    .borrow_mut()
+                    .assign(name, Value::Class(Rc::new(class)))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Loads the module at `path`, running it the first time it is imported.
+    fn import(&mut self, path: &Token) -> Result<Rc<LoxModule>, Unwind> {
+        let relative = path.literal.as_deref().unwrap_or_default();
+        let current_file = self
+            .files
+            .iter()
+            .find(|(globals, _)| Rc::ptr_eq(globals, &self.globals))
+            .map(|(_, file)| file);
+        let full_path = match current_file.and_then(|file| file.parent()) {
+            Some(directory) => directory.join(relative),
+            None => PathBuf::from(relative),
+        };
+        // Without file access a script may only import files from its own directory tree.
+        // The path is checked before touching the file system, so a denied import doesn't
+        // even reveal whether the file exists, and again once symlinks are resolved.
+        let sandbox = match self.importing.first().and_then(|file| file.parent()) {
+            Some(root) if !self.permissions.fs => Some(root.to_path_buf()),
+            Some(_) => None,
+            None if !self.permissions.fs => Some(PathBuf::new()),
+            None => None,
+        };
+        let outside_sandbox = |file: &Path| {
+            sandbox
+                .as_ref()
+                .is_some_and(|root| root.as_os_str().is_empty() || !file.starts_with(root))
+        };
+        if outside_sandbox(&normalize(&full_path)) {
+            return Err(operand_error(path, &sandbox_error(relative)));
+        }
+        let canonical = fs::canonicalize(&full_path).map_err(|e| {
+            operand_error(
+                path,
+                &format!("Could not read module \"{}\": {}.", relative, e),
+            )
+        })?;
+        if outside_sandbox(&canonical) {
+            return Err(operand_error(path, &sandbox_error(relative)));
+        }
+        if let Some(module) = self.modules.get(&canonical) {
+            return Ok(Rc::clone(module));
+        }
+        if let Some(start) = self.importing.iter().position(|file| *file == canonical) {
+            let cycle: Vec<String> = self.importing[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|file| file_name(file))
+                .collect();
+            return Err(operand_error(
+                path,
+                &format!("Import cycle: {}.", cycle.join(" -> ")),
+            ));
+        }
+
+        let source = fs::read_to_string(&canonical).map_err(|e| {
+            operand_error(
+                path,
+                &format!("Could not read module \"{}\": {}.", relative, e),
+            )
+        })?;
+        let mut lexer = Lexer::from_source(&source);
+        let (statements, locals) = Parser::new(&mut lexer)
+            .parse()
+            .and_then(|statements| {
+                let locals = Resolver::new().resolve(&statements)?;
+                Ok((statements, locals))
+            })
+            .map(|(statements, locals)| {
+                let statements = if self.optimize {
+                    optimizer::optimize(&statements)
+                } else {
+                    statements
+                };
+                (statements, locals)
+            })
+            .map_err(|errors| {
+                operand_error(
+                    path,
+                    &format!("Error in module \"{}\": {}", relative, errors[0]),
+                )
+            })?;
+
+        // The module runs at its own top level, with globals of its own.
+        self.allocate(size_of::<Environment>())?;
+        let globals = Environment::new(None);
+        let previous_globals = std::mem::replace(&mut self.globals, Rc::clone(&globals));
+        let previous_environment = std::mem::replace(&mut self.environment, Rc::clone(&globals));
+        let previous_locals = std::mem::replace(&mut self.locals, Rc::new(locals));
+        natives::define_globals(self);
+        self.files.push((Rc::clone(&globals), canonical.clone()));
+        self.importing.push(canonical.clone());
+        let result = self.interpret(&statements);
+        self.importing.pop();
+        self.globals = previous_globals;
+        self.environment = previous_environment;
+        self.locals = previous_locals;
+        if result.is_err() {
+            self.files
+                .retain(|(file_globals, _)| !Rc::ptr_eq(file_globals, &globals));
+        }
+        result?;
+
+        let exports = statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Stmt::Var { name, .. } | Stmt::Class { name, .. } => Some(name.lexeme.clone()),
+                Stmt::Function { decl } => Some(decl.name.lexeme.clone()),
+                _ => None,
+            })
+            .collect();
+        let module = Rc::new(LoxModule {
+            name: file_name(&canonical).trim_end_matches(".lox").to_string(),
+            globals,
+            exports,
+        });
+        self.modules.insert(canonical, Rc::clone(&module));
+        Ok(module)
+    }
+
+    // Runs statements in a new scope nested in the current one.
+    fn execute_scoped(&mut self, statements: &[Stmt]) -> Result<(), Unwind> {
+        self.allocate(size_of::<Environment>())?;
+        let environment = Environment::new(Some(Rc::clone(&self.environment)));
+        self.execute_block(statements, environment)
+    }
+
+    /// The calls being run, innermost first, ending with the top level of the script. Natives
+    /// can use this to see where they were called from.
+    pub fn call_stack(&self) -> Vec<StackFrame> {
+        self.stack_trace(self.line)
+    }
+
+    // Records the calls that led to a runtime error, the first time the error leaves a
+    // function or reaches the top level.
+    fn traced(&self, unwind: Unwind) -> Unwind {
+        match unwind {
+            Unwind::Error(mut error) if error.trace.is_empty() => {
+                error.trace = self.stack_trace(error.line);
+                Unwind::Error(error)
+            }
+            other => other,
+        }
+    }
+
+    // The calls that led to `line`, innermost first.
+    fn stack_trace(&self, line: usize) -> Vec<StackFrame> {
+        let mut trace = Vec::with_capacity(self.calls.len() + 1);
+        let mut line = line;
+        for (function, call_line) in self.calls.iter().rev() {
+            trace.push(StackFrame {
+                function: Some(function.name.lexeme.clone()),
+                line,
+            });
+            line = *call_line;
+        }
+        trace.push(StackFrame {
+            function: None,
+            line,
+        });
+        trace
+    }
+
+    /// Runs `statements` inside `environment`, restoring the current environment afterwards
+    /// even when execution unwinds.
+    pub fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<(), Unwind> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let mut result = Ok(());
+        for statement in statements {
+            result = self.execute(statement);
+            if result.is_err() {
+                break;
+            }
+        }
+        self.environment = previous;
+        result
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, Unwind> {
+        self.tick(expr)?;
+        match expr {
+            Expr::Literal { value, .. } => Ok(match value {
+                LiteralValue::Nil => Value::Nil,
+                LiteralValue::Bool(b) => Value::Bool(*b),
+                LiteralValue::Number(n) => Value::Number(*n),
+                LiteralValue::String(s) => Value::String(Rc::from(s.as_str())),
+            }),
+            Expr::Grouping { expr } => self.evaluate(expr),
+            Expr::Stringify { expr } => {
+                let string = self.evaluate(expr)?.to_string();
+                self.allocate(string.len())?;
+                Ok(Value::String(Rc::from(string)))
+            }
+            Expr::Unary { op, right } => {
+                let right = self.evaluate(right)?;
+                match op.token_type {
+                    TokenType::Bang => Ok(Value::Bool(!right.is_truthy())),
+                    TokenType::Minus => match right {
+                        Value::Number(n) => Ok(Value::Number(-n)),
+                        _ => Err(operand_error(op, "Operand must be a number.")),
+                    },
+                    _ => unreachable!("parser only produces '!' and '-' unary operators"),
+                }
+            }
+            Expr::Binary { left, op, right } => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                self.binary(op, left, right)
+            }
+            Expr::Logical { left, op, right } => {
+                let left = self.evaluate(left)?;
+                let short_circuits = if op.token_type == TokenType::Or {
+                    left.is_truthy()
+                } else {
+                    !left.is_truthy()
+                };
+                if short_circuits {
+                    Ok(left)
+                } else {
+                    self.evaluate(right)
+                }
+            }
+            Expr::Variable { id, name } => self.look_up_variable(*id, name),
+            Expr::Assign { id, name, value } => {
+                let value = self.evaluate(value)?;
+                match self.locals.get(id) {
+                    Some(distance) => Environment::assign_at(
+                        &self.environment,
+                        *distance,
+                        &name.lexeme,
+                        value.clone(),
+                    ),
+                    None => self.globals.borrow_mut().assign(name, value.clone())?,
+                }
+                Ok(value)
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                let callee = self.evaluate(callee)?;
+                let mut args = Vec::with_capacity(arguments.len());
+                for argument in arguments {
+                    args.push(self.evaluate(argument)?);
+                }
+                self.call_value(callee, args, paren.line)
+            }
+            Expr::Get { object, name } => {
+                let object = self.evaluate(object)?;
+                match object {
+                    Value::Instance(ref instance) => self.instance_get(instance, name),
+                    Value::List(ref list) => match natives::list_method(list, &name.lexeme) {
+                        Some(method) => Ok(Value::Native(Rc::new(method))),
+                        None => Err(operand_error(
+                            name,
+                            &format!("Undefined property '{}'.", name.lexeme),
+                        )),
+                    },
+                    Value::String(ref string) => match natives::string_method(string, &name.lexeme)
+                    {
+                        Some(method) => Ok(Value::Native(Rc::new(method))),
+                        None => Err(operand_error(
+                            name,
+                            &format!("Undefined property '{}'.", name.lexeme),
+                        )),
+                    },
+                    Value::Module(ref module) => module
+                        .get(&name.lexeme)
+                        .ok_or_else(|| operand_error(name, &no_export(module, &name.lexeme))),
+                    Value::Error(ref error) => match name.lexeme.as_str() {
+                        "message" => Ok(Value::from(error.message.as_str())),
+                        "line" => Ok(Value::Number(error.line as f64)),
+                        "value" => Ok(error.value.clone()),
+                        _ => Err(operand_error(
+                            name,
+                            &format!("Undefined property '{}'.", name.lexeme),
+                        )),
+                    },
+                    Value::Map(ref map) => match natives::map_method(map, &name.lexeme) {
+                        Some(method) => Ok(Value::Native(Rc::new(method))),
+                        None => Err(operand_error(
+                            name,
+                            &format!("Undefined property '{}'.", name.lexeme),
+                        )),
+                    },
+                    _ => Err(operand_error(name, "Only instances have properties.")),
+                }
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                let object = self.evaluate(object)?;
+                let Value::Instance(instance) = object else {
+                    return Err(operand_error(name, "Only instances have fields."));
+                };
+                let value = self.evaluate(value)?;
+                self.allocate(size_of::<Value>() + name.lexeme.len())?;
+                instance
+                    .borrow_mut()
+                    .fields
+                    .insert(name.lexeme.clone(), value.clone());
+                Ok(value)
+            }
+            Expr::List { elements, .. } => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.evaluate(element)?);
+                }
+                self.allocate(values.len() * size_of::<Value>())?;
+                Ok(Value::from(values))
+            }
+            Expr::Map { entries, .. } => {
+                let mut map = LoxMap::default();
+                for (key, value) in entries {
+                    let key_line = key.line().unwrap_or(self.line);
+                    let key = natives::map_key(&self.evaluate(key)?)
+                        .map_err(|error| error.at_line(key_line))?;
+                    map.insert(key, self.evaluate(value)?);
+                }
+                self.allocate(map.len() * 2 * size_of::<Value>())?;
+                Ok(Value::Map(Rc::new(RefCell::new(map))))
+            }
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                self.index_get(&object, &index)
+                    .map_err(|error| error.at_line(bracket.line))
+            }
+            Expr::SetIndex {
+                object,
+                bracket,
+                index,
+                value,
+            } => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                let value = self.evaluate(value)?;
+                self.index_set(&object, &index, value.clone())
+                    .map_err(|error| error.at_line(bracket.line))?;
+                Ok(value)
+            }
+            Expr::This { id, keyword } => self.look_up_variable(*id, keyword),
+            Expr::Super {
+                id,
+                keyword,
+                method,
+            } => {
+                let distance = self.locals.get(id).copied().unwrap_or(0);
+                let superclass = Environment::get_at(&self.environment, distance, "super");
+                // "this" is always bound one scope inside the one holding "super".
+                let object = Environment::get_at(&self.environment, distance - 1, "this");
+                let Value::Class(superclass) = superclass else {
+                    return Err(operand_error(keyword, "Superclass must be a class."));
+                };
+                match superclass.find_method(&method.lexeme) {
+                    Some(found) => Ok(Value::Function(Rc::new(found.bind(object)))),
+                    None => Err(operand_error(
+                        method,
+                        &format!("Undefined property '{}'.", method.lexeme),
+                    )),
+                }
+            }
+        }
+    }
+
+    fn binary(&mut self, op: &Token, left: Value, right: Value) -> Result<Value, Unwind> {
+        match op.token_type {
+            TokenType::EqualEqual => return Ok(Value::Bool(left == right)),
+            TokenType::BangEqual => return Ok(Value::Bool(left != right)),
+            TokenType::Plus => {
+                return match (left, right) {
+                    (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
+                    (Value::String(a), Value::String(b)) => {
+                        self.allocate(a.len() + b.len())?;
+                        Ok(Value::String(Rc::from(format!("{}{}", a, b))))
+                    }
+                    _ => Err(operand_error(
+                        op,
+                        "Operands must be two numbers or two strings.",
+                    )),
+                };
+            }
+            _ => {}
+        }
+
+        let (Value::Number(a), Value::Number(b)) = (left, right) else {
+            return Err(operand_error(op, "Operands must be numbers."));
+        };
+        Ok(match op.token_type {
+            TokenType::Minus => Value::Number(a - b),
+            TokenType::Star => Value::Number(a * b),
+            TokenType::Slash => Value::Number(a / b),
+            // The remainder takes the sign of the dividend, as in C and JavaScript.
+            TokenType::Percent => Value::Number(a % b),
+            TokenType::Greater => Value::Bool(a > b),
+            TokenType::GreaterEqual => Value::Bool(a >= b),
+            TokenType::Less => Value::Bool(a < b),
+            TokenType::LessEqual => Value::Bool(a <= b),
+            _ => unreachable!("parser only produces known binary operators"),
+        })
+    }
+
+    fn look_up_variable(&self, id: usize, name: &Token) -> Result<Value, Unwind> {
+        match self.locals.get(&id) {
+            Some(distance) => Ok(Environment::get_at(
+                &self.environment,
+                *distance,
+                &name.lexeme,
+            )),
+            None => Ok(self.globals.borrow().get(name)?),
+        }
+    }
+
+    // The element of a list or the value of a map at `index`.
+    fn index_get(&mut self, object: &Value, index: &Value) -> Result<Value, Unwind> {
+        match object {
+            Value::List(list) => {
+                let list = list.borrow();
+                Ok(list[natives::list_index(index, list.len())?].clone())
+            }
+            Value::Map(map) => {
+                let key = natives::map_key(index)?;
+                map.borrow()
+                    .get(&key)
+                    .cloned()
+                    .ok_or_else(|| Unwind::error(&format!("Undefined key {:?}.", index)))
+            }
+            _ => Err(Unwind::error("Only lists and maps can be indexed.")),
+        }
+    }
+
+    fn index_set(&mut self, object: &Value, index: &Value, value: Value) -> Result<(), Unwind> {
+        match object {
+            Value::List(list) => {
+                let mut list = list.borrow_mut();
+                let i = natives::list_index(index, list.len())?;
+                list[i] = value;
+            }
+            Value::Map(map) => {
+                let key = natives::map_key(index)?;
+                if map.borrow_mut().insert(key, value) {
+                    self.allocate(2 * size_of::<Value>())?;
+                }
+            }
+            _ => return Err(Unwind::error("Only lists and maps can be indexed.")),
+        }
+        Ok(())
+    }
+
+    fn instance_get(
+        &mut self,
+        instance: &Rc<RefCell<LoxInstance>>,
+        name: &Token,
+    ) -> Result<Value, Unwind> {
+        property(instance, &name.lexeme)
+            .ok_or_else(|| operand_error(name, &format!("Undefined property '{}'.", name.lexeme)))
+    }
+
+    // Starts a `for (var x in ...)` loop over the value.
+    fn iteration(&mut self, iterable: Value, line: usize) -> Result<Iteration, Unwind> {
+        Ok(match iterable {
+            Value::List(list) => Iteration::List(list, 0),
+            Value::Map(map) => {
+                let keys: Vec<Value> = map.borrow().iter().map(|(key, _)| key.to_value()).collect();
+                self.allocate(keys.len() * size_of::<Value>())?;
+                Iteration::Keys(keys.into_iter())
+            }
+            Value::String(string) => Iteration::Chars(string, 0),
+            Value::Range(range) => Iteration::Range(range, 0.0),
+            // An iterable object hands out a fresh iterator from `iterator()`, but an object
+            // that is its own iterator can be looped over too.
+            Value::Instance(ref instance) => {
+                let iterator = match property(instance, "iterator") {
+                    Some(method) => self.call_value(method, Vec::new(), line)?,
+                    None => iterable.clone(),
+                };
+                let Value::Instance(iterator) = iterator else {
+                    return Err(Unwind::error("iterator() must return an instance."));
+                };
+                match (property(&iterator, "hasNext"), property(&iterator, "next")) {
+                    (Some(has_next), Some(next)) => Iteration::Object { has_next, next },
+                    _ => {
+                        return Err(Unwind::error(
+                            "Iterators must have 'hasNext' and 'next' methods.",
+                        ));
+                    }
+                }
+            }
+            other => {
+                return Err(Unwind::error(&format!(
+                    "Can't iterate over {}.",
+                    other.type_name()
+                )));
+            }
+        })
+    }
+
+    // The next item of a `for (var x in ...)` loop, or None when it is done.
+    fn next_item(
+        &mut self,
+        iteration: &mut Iteration,
+        line: usize,
+    ) -> Result<Option<Value>, Unwind> {
+        Ok(match iteration {
+            Iteration::List(list, i) => {
+                let item = list.borrow().get(*i).cloned();
+                *i += 1;
+                item
+            }
+            Iteration::Keys(keys) => keys.next(),
+            Iteration::Chars(string, i) => match string[*i..].chars().next() {
+                Some(c) => {
+                    *i += c.len_utf8();
+                    self.allocate(c.len_utf8())?;
+                    Some(Value::from(c.to_string()))
+                }
+                None => None,
+            },
+            Iteration::Range(range, count) => {
+                let n = range.start + *count;
+                if n >= range.end {
+                    return Ok(None);
+                }
+                *count += 1.0;
+                Some(Value::Number(n))
+            }
+            Iteration::Object { has_next, next } => {
+                if self
+                    .call_value(has_next.clone(), Vec::new(), line)?
+                    .is_truthy()
+                {
+                    Some(self.call_value(next.clone(), Vec::new(), line)?)
+                } else {
+                    None
+                }
+            }
+        })
+    }
+
+    /// Calls any callable value. `line` is the line of the call, used for error reporting.
+    pub fn call_value(
+        &mut self,
+        callee: Value,
+        args: Vec<Value>,
+        line: usize,
+    ) -> Result<Value, Unwind> {
+        let arity = match &callee {
+            Value::Function(function) => function.arity(),
+            Value::Native(native) => native.arity,
+            Value::Class(class) => class.arity(),
+            _ => {
+                return Err(RuntimeError::new(
+                    "Can only call functions and classes.".to_string(),
+                    line,
+                )
+                .into());
+            }
+        };
+        if args.len() != arity {
+            return Err(RuntimeError::new(
+                format!("Expected {} arguments but got {}.", arity, args.len()),
+                line,
+            )
+            .into());
+        }
+
+        self.check_interrupt(line)?;
+        self.check_stack(line)?;
+        if self.call_depth >= self.limits.max_call_depth {
+            return Err(RuntimeError::with_kind(
+                RuntimeErrorKind::StackOverflow,
+                "Stack overflow.".to_string(),
+                line,
+            )
+            .into());
+        }
+        self.call_depth += 1;
+        let result = self.call_checked(callee, args, line);
+        self.call_depth -= 1;
+        result
+    }
+
+    fn call_checked(
+        &mut self,
+        callee: Value,
+        args: Vec<Value>,
+        line: usize,
+    ) -> Result<Value, Unwind> {
+        match callee {
+            Value::Function(function) => {
+                self.calls.push((Rc::clone(&function.declaration), line));
+                let result = self.call_function(&function, args);
+                let result = result.map_err(|unwind| self.traced(unwind));
+                self.calls.pop();
+                result
+            }
+            Value::Native(native) => {
+                (native.function)(self, &args).map_err(|unwind| unwind.at_line(line))
+            }
+            Value::Class(class) => {
+                self.allocate(size_of::<LoxInstance>())?;
+                let instance = Value::Instance(Rc::new(RefCell::new(LoxInstance {
+                    class: Rc::clone(&class),
+                    fields: HashMap::new(),
+                })));
+                if let Some(initializer) = class.find_method("init") {
+                    self.calls.push((Rc::clone(&initializer.declaration), line));
+                    let result = self.call_function(&initializer.bind(instance.clone()), args);
+                    let result = result.map_err(|unwind| self.traced(unwind));
+                    self.calls.pop();
+                    result?;
+                }
+                Ok(instance)
+            }
+            _ => unreachable!("non-callable values are rejected above"),
+        }
+    }
+
+    fn call_function(&mut self, function: &LoxFunction, args: Vec<Value>) -> Result<Value, Unwind> {
+        self.allocate(size_of::<Environment>() + args.len() * size_of::<Value>())?;
+        let environment = Environment::new(Some(Rc::clone(&function.closure)));
+        for (param, arg) in function.declaration.params.iter().zip(args) {
+            environment.borrow_mut().define(&param.lexeme, arg);
+        }
+
+        // Functions from a module see that module's globals, wherever they are called from.
+        let globals = Environment::root(&function.closure);
+        let previous_globals = std::mem::replace(&mut self.globals, globals);
+        let previous_locals = std::mem::replace(&mut self.locals, Rc::clone(&function.locals));
+        let result = self.execute_block(&function.declaration.body, environment);
+        self.globals = previous_globals;
+        self.locals = previous_locals;
+        let result = match result {
+            Ok(()) => Value::Nil,
+            Err(Unwind::Return(value)) => value,
+            Err(other) => return Err(other),
+        };
+
+        // An initializer always hands back the instance, even on an early bare return.
+        if function.is_initializer {
+            return Ok(Environment::get_at(&function.closure, 0, "this"));
+        }
+        Ok(result)
+    }
+}
+
+// The error a `catch` clause receives for an unwind, or the unwind itself if it isn't an
+// error that scripts may catch. Running out of a resource limit stops the script for good.
+fn caught(unwind: Unwind) -> Result<Rc<LoxError>, Unwind> {
+    match unwind {
+        Unwind::Throw(error) => Ok(error),
+        Unwind::Error(error) if error.kind == RuntimeErrorKind::Error => Ok(Rc::new(LoxError {
+            value: Value::from(error.message.as_str()),
+            message: error.message,
+            line: error.line,
+            trace: error.trace,
+        })),
+        other => Err(other),
+    }
+}
+
+// Roughly where the stack currently ends.
+fn stack_address() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+fn no_export(module: &LoxModule, name: &str) -> String {
+    format!("Module '{}' has no export '{}'.", module.name, name)
+}
+
+fn sandbox_error(path: &str) -> String {
+    format!(
+        "Can't import \"{}\" from outside the script's directory. Run with --allow-fs to allow it.",
+        path
+    )
+}
+
+// Removes `.` and `..` components without looking at the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+// Handles how one pass through a loop body ended, returning whether the loop goes on.
+fn loop_body(result: Result<(), Unwind>) -> Result<bool, Unwind> {
+    match result {
+        Ok(()) | Err(Unwind::Continue) => Ok(true),
+        Err(Unwind::Break) => Ok(false),
+        Err(other) => Err(other),
+    }
+}
+
+// Where a `for (var x in ...)` loop is up to.
+enum Iteration {
+    // Lists are read by position, so elements pushed during the loop are visited too.
+    List(Rc<RefCell<Vec<Value>>>, usize),
+    // The keys of a map as they were when the loop started.
+    Keys(std::vec::IntoIter<Value>),
+    // A string and the byte offset of its next character.
+    Chars(Rc<str>, usize),
+    // A range and how many of its numbers have been visited.
+    Range(LoxRange, f64),
+    // The bound `hasNext` and `next` methods of an iterator object.
+    Object { has_next: Value, next: Value },
+}
+
+// A field of the instance, or a method of its class bound to it.
+fn property(instance: &Rc<RefCell<LoxInstance>>, name: &str) -> Option<Value> {
+    if let Some(value) = instance.borrow().fields.get(name) {
+        return Some(value.clone());
+    }
+    let class = Rc::clone(&instance.borrow().class);
+    let method = class.find_method(name)?;
+    Some(Value::Function(Rc::new(
+        method.bind(Value::Instance(Rc::clone(instance))),
+    )))
+}
+
+fn operand_error(token: &Token, message: &str) -> Unwind {
+    Unwind::Error(RuntimeError::new(message.to_string(), token.line))
+}
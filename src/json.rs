@@ -0,0 +1,334 @@
+use std::fmt::Formatter;
+
+/// A JSON value, just enough of it for the language server's messages. Objects keep their
+/// fields in order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = JsonParser {
+            chars: text.chars().collect(),
+            pointer: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pointer < parser.chars.len() {
+            return Err(parser.error("Expect end of input."));
+        }
+        Ok(value)
+    }
+
+    pub fn object<const N: usize>(fields: [(&str, Json); N]) -> Json {
+        Json::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// Looks up a field of an object, `None` for missing fields and anything not an object.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Self {
+        Json::Bool(b)
+    }
+}
+
+impl From<f64> for Json {
+    fn from(n: f64) -> Self {
+        Json::Number(n)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Self {
+        Json::Number(n as f64)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::String(s.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Json::String(s)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(items: Vec<Json>) -> Self {
+        Json::Array(items)
+    }
+}
+
+impl std::fmt::Display for Json {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) if n.is_finite() => write!(f, "{}", n),
+            // JSON has no infinities or NaN.
+            Json::Number(_) => write!(f, "null"),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut Formatter<'_>, s: &str) -> Result<(), std::fmt::Error> {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct JsonParser {
+    chars: Vec<char>,
+    pointer: usize,
+}
+
+impl JsonParser {
+    fn error(&self, message: &str) -> String {
+        format!("{} (at character {})", message, self.pointer)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .chars
+            .get(self.pointer)
+            .is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+        {
+            self.pointer += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.chars.get(self.pointer) == Some(&c) {
+            self.pointer += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expect '{}'.", c)))
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        for expected in word.chars() {
+            if self.chars.get(self.pointer) != Some(&expected) {
+                return Err(self.error("Unexpected character."));
+            }
+            self.pointer += 1;
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.chars.get(self.pointer) {
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('[') => {
+                self.pointer += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.chars.get(self.pointer) == Some(&']') {
+                    self.pointer += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.chars.get(self.pointer) {
+                        Some(',') => self.pointer += 1,
+                        Some(']') => {
+                            self.pointer += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => return Err(self.error("Expect ',' or ']'.")),
+                    }
+                }
+            }
+            Some('{') => {
+                self.pointer += 1;
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.chars.get(self.pointer) == Some(&'}') {
+                    self.pointer += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.chars.get(self.pointer) != Some(&'"') {
+                        return Err(self.error("Expect a string key."));
+                    }
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    fields.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.chars.get(self.pointer) {
+                        Some(',') => self.pointer += 1,
+                        Some('}') => {
+                            self.pointer += 1;
+                            return Ok(Json::Object(fields));
+                        }
+                        _ => return Err(self.error("Expect ',' or '}'.")),
+                    }
+                }
+            }
+            Some(c) if *c == '-' || c.is_ascii_digit() => self.number(),
+            _ => Err(self.error("Expect a value.")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pointer;
+        while self
+            .chars
+            .get(self.pointer)
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            self.pointer += 1;
+        }
+        let text: String = self.chars[start..self.pointer].iter().collect();
+        text.parse()
+            .map(Json::Number)
+            .map_err(|_| self.error("Invalid number."))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        // Skip the opening quote.
+        self.pointer += 1;
+        let mut out = String::new();
+        loop {
+            let Some(&c) = self.chars.get(self.pointer) else {
+                return Err(self.error("Unterminated string."));
+            };
+            self.pointer += 1;
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let Some(&escape) = self.chars.get(self.pointer) else {
+                        return Err(self.error("Unterminated string."));
+                    };
+                    self.pointer += 1;
+                    match escape {
+                        '"' | '\\' | '/' => out.push(escape),
+                        'b' => out.push('\u{8}'),
+                        'f' => out.push('\u{c}'),
+                        'n' => out.push('\n'),
+                        'r' => out.push('\r'),
+                        't' => out.push('\t'),
+                        'u' => {
+                            let high = self.hex4()?;
+                            // Characters outside the basic plane come as a surrogate pair.
+                            let code = if (0xd800..0xdc00).contains(&high)
+                                && self.chars.get(self.pointer) == Some(&'\\')
+                                && self.chars.get(self.pointer + 1) == Some(&'u')
+                            {
+                                self.pointer += 2;
+                                let low = self.hex4()?;
+                                0x10000
+                                    + ((high - 0xd800) << 10)
+                                    + (low.wrapping_sub(0xdc00) & 0x3ff)
+                            } else {
+                                high
+                            };
+                            out.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                        }
+                        _ => return Err(self.error("Invalid escape.")),
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits: String = self
+            .chars
+            .get(self.pointer..self.pointer + 4)
+            .ok_or_else(|| self.error("Invalid unicode escape."))?
+            .iter()
+            .collect();
+        self.pointer += 4;
+        u32::from_str_radix(&digits, 16).map_err(|_| self.error("Invalid unicode escape."))
+    }
+}
@@ -1,19 +1,27 @@
 use std::char;
+use std::collections::HashMap;
 use std::fmt::Formatter;
 use std::fs::File;
 use std::io::{self, Read};
 
+use crate::interpreter::RuntimeError;
+use crate::parser::CompileError;
+
 #[allow(dead_code)]
-#[derive(PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TokenType {
     // Single-character tokens.
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
+    Colon,
     Dot,
     Minus,
+    Percent,
     Plus,
     Semicolon,
     Slash,
@@ -33,58 +41,129 @@ pub enum TokenType {
     Identifier,
     String,
     Number,
+    // The part of an interpolated string up to a `${`, or between a `}` and the next `${`.
+    // The expression inside comes next, and the string ends with a `String` token whose
+    // lexeme starts at the closing `}`.
+    Interpolation,
 
     // Keywords.
     And,
+    Break,
+    Catch,
     Class,
+    Continue,
     Else,
     False,
+    Finally,
+    From,
     Fun,
     For,
     If,
+    Import,
+    In,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
-    Keyword,
 
-    // Error.
-    Error,
-}
+    // Trivia, only produced when the lexer is asked to keep it. A `//` or `/* */` comment, a
+    // run of spaces, tabs and carriage returns, or a single line feed.
+    Comment,
+    Whitespace,
+    Newline,
 
-fn report_error(line: usize, message: String) {
-    eprintln!("Error occured on line {}, {}", line, message);
-    std::process::exit(65);
+    // End of input. The lexer never produces this, the CST uses it to hold trailing trivia.
+    Eof,
+
+    // Error. The lexeme holds the error message.
+    Error,
 }
 
 #[derive(Debug)]
 pub enum RoxError {
     InvalidToken,
     IOError(io::Error),
+    CompileError(Vec<CompileError>),
+    RuntimeError(RuntimeError),
+    // The script called exit() with this code.
+    Exit(i32),
 }
 
+// Exit codes follow the sysexits convention used by the reference Lox implementations.
+pub const EXIT_USAGE: i32 = 64;
+pub const EXIT_COMPILE_ERROR: i32 = 65;
+pub const EXIT_RUNTIME_ERROR: i32 = 70;
+pub const EXIT_IO_ERROR: i32 = 74;
+
+impl RoxError {
+    /// The process exit code `rox run` uses for this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RoxError::InvalidToken | RoxError::CompileError(_) => EXIT_COMPILE_ERROR,
+            RoxError::IOError(_) => EXIT_IO_ERROR,
+            RoxError::RuntimeError(_) => EXIT_RUNTIME_ERROR,
+            RoxError::Exit(code) => *code,
+        }
+    }
+}
+
+impl std::fmt::Display for RoxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            RoxError::InvalidToken => write!(f, "Invalid token."),
+            RoxError::IOError(e) => write!(f, "{}", e),
+            RoxError::CompileError(errors) => {
+                let lines: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            RoxError::RuntimeError(error) => write!(f, "{}", error),
+            RoxError::Exit(code) => write!(f, "Script exited with code {}.", code),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
-    line: usize,
+    pub line: usize,
+    /// Where the token starts on its line, counting chars from 1.
+    pub column: usize,
+    /// The value of a string literal, with its escape sequences decoded. The lexeme keeps
+    /// the source text, quotes included. None for every other token.
+    pub literal: Option<String>,
 }
 
 pub struct Lexer {
     chars: Vec<char>,
     pointer: usize,
     current_line: usize,
-    keyword_list: Vec<String>,
+    // Where the current line starts, to work out token columns.
+    line_start: usize,
+    keyword_list: HashMap<String, TokenType>,
+    keep_comments: bool,
+    keep_whitespace: bool,
+    // A token lexed along with the previous one, returned by the next call.
+    pending: Option<Token>,
+    // For each string interpolation being lexed, innermost last, how many braces are open
+    // inside its expression.
+    interpolations: Vec<usize>,
 }
 
 impl Iterator for Lexer {
     type Item = Token;
     fn next(&mut self) -> Option<Token> {
-        let mut next_token: Option<Token> = Option::None;
+        if let Some(token) = self.pending.take() {
+            return Some(token);
+        }
+        let next_token: Option<Token>;
 
         // A loop here makes it so that I don't have to return the next_token in every single
         // arm. I can 'continue' in certain arms like whitespaces.
@@ -94,245 +173,395 @@ impl Iterator for Lexer {
                     // Single character tokens
                     let lexeme = c.to_string();
                     let line = self.current_line;
+                    let column = self.pointer - self.line_start + 1;
                     match c {
                         '(' => {
                             next_token = Some(Token {
                                 token_type: TokenType::LeftParen,
-                                lexeme: lexeme,
-                                line: line,
+                                lexeme,
+                                line,
+                                column,
+                                literal: None,
                             });
                             self.pointer += 1;
                         }
                         ')' => {
                             next_token = Some(Token {
                                 token_type: TokenType::RightParen,
-                                lexeme: lexeme,
-                                line: line,
+                                lexeme,
+                                line,
+                                column,
+                                literal: None,
                             });
                             self.pointer += 1;
                         }
                         '{' => {
+                            if let Some(depth) = self.interpolations.last_mut() {
+                                *depth += 1;
+                            }
                             next_token = Some(Token {
                                 token_type: TokenType::LeftBrace,
-                                lexeme: lexeme,
-                                line: line,
+                                lexeme,
+                                line,
+                                column,
+                                literal: None,
                             });
                             self.pointer += 1;
                         }
+                        // This brace closes an interpolated expression, the string goes on.
+                        '}' if self.interpolations.last() == Some(&0) => {
+                            self.interpolations.pop();
+                            next_token = Some(self.string(line, column));
+                        }
                         '}' => {
+                            if let Some(depth) = self.interpolations.last_mut() {
+                                *depth -= 1;
+                            }
                             next_token = Some(Token {
                                 token_type: TokenType::RightBrace,
-                                lexeme: lexeme,
-                                line: line,
+                                lexeme,
+                                line,
+                                column,
+                                literal: None,
+                            });
+                            self.pointer += 1;
+                        }
+                        '[' => {
+                            next_token = Some(Token {
+                                token_type: TokenType::LeftBracket,
+                                lexeme,
+                                line,
+                                column,
+                                literal: None,
+                            });
+                            self.pointer += 1;
+                        }
+                        ']' => {
+                            next_token = Some(Token {
+                                token_type: TokenType::RightBracket,
+                                lexeme,
+                                line,
+                                column,
+                                literal: None,
                             });
                             self.pointer += 1;
                         }
                         ',' => {
                             next_token = Some(Token {
                                 token_type: TokenType::Comma,
-                                lexeme: lexeme,
-                                line: line,
+                                lexeme,
+                                line,
+                                column,
+                                literal: None,
                             });
                             self.pointer += 1;
                         }
                         '.' => {
                             next_token = Some(Token {
                                 token_type: TokenType::Dot,
-                                lexeme: lexeme,
-                                line: line,
+                                lexeme,
+                                line,
+                                column,
+                                literal: None,
                             });
                             self.pointer += 1;
                         }
                         '-' => {
                             next_token = Some(Token {
                                 token_type: TokenType::Minus,
-                                lexeme: lexeme,
-                                line: line,
+                                lexeme,
+                                line,
+                                column,
+                                literal: None,
                             });
                             self.pointer += 1;
                         }
                         '+' => {
                             next_token = Some(Token {
                                 token_type: TokenType::Plus,
-                                lexeme: lexeme,
-                                line: line,
+                                lexeme,
+                                line,
+                                column,
+                                literal: None,
+                            });
+                            self.pointer += 1;
+                        }
+                        ':' => {
+                            next_token = Some(Token {
+                                token_type: TokenType::Colon,
+                                lexeme,
+                                line,
+                                column,
+                                literal: None,
                             });
                             self.pointer += 1;
                         }
                         ';' => {
                             next_token = Some(Token {
                                 token_type: TokenType::Semicolon,
-                                lexeme: lexeme,
-                                line: line,
+                                lexeme,
+                                line,
+                                column,
+                                literal: None,
+                            });
+                            self.pointer += 1;
+                        }
+                        '%' => {
+                            next_token = Some(Token {
+                                token_type: TokenType::Percent,
+                                lexeme,
+                                line,
+                                column,
+                                literal: None,
                             });
                             self.pointer += 1;
                         }
                         '*' => {
                             next_token = Some(Token {
                                 token_type: TokenType::Star,
-                                lexeme: lexeme,
-                                line: line,
+                                lexeme,
+                                line,
+                                column,
+                                literal: None,
                             });
                             self.pointer += 1;
                         }
                         '=' => {
-                            if self.peek_one_char() == '=' {
+                            if self.peek_one_char() == Some('=') {
                                 next_token = Some(Token {
                                     token_type: TokenType::EqualEqual,
                                     lexeme: "==".to_string(),
-                                    line: line,
+                                    line,
+                                    column,
+                                    literal: None,
                                 });
                                 self.pointer += 2;
                             } else {
                                 next_token = Some(Token {
                                     token_type: TokenType::Equal,
                                     lexeme: "=".to_string(),
-                                    line: line,
+                                    line,
+                                    column,
+                                    literal: None,
                                 });
                                 self.pointer += 1;
                             }
                         }
                         '<' => {
-                            if self.peek_one_char() == '=' {
+                            if self.peek_one_char() == Some('=') {
                                 next_token = Some(Token {
                                     token_type: TokenType::LessEqual,
                                     lexeme: "<=".to_string(),
-                                    line: line,
+                                    line,
+                                    column,
+                                    literal: None,
                                 });
                                 self.pointer += 2;
                             } else {
                                 next_token = Some(Token {
                                     token_type: TokenType::Less,
                                     lexeme: "<".to_string(),
-                                    line: line,
+                                    line,
+                                    column,
+                                    literal: None,
                                 });
                                 self.pointer += 1;
                             }
                         }
                         '>' => {
-                            if self.peek_one_char() == '=' {
+                            if self.peek_one_char() == Some('=') {
                                 next_token = Some(Token {
                                     token_type: TokenType::GreaterEqual,
                                     lexeme: ">=".to_string(),
-                                    line: line,
+                                    line,
+                                    column,
+                                    literal: None,
                                 });
                                 self.pointer += 2;
                             } else {
                                 next_token = Some(Token {
                                     token_type: TokenType::Greater,
                                     lexeme: ">".to_string(),
-                                    line: line,
+                                    line,
+                                    column,
+                                    literal: None,
                                 });
                                 self.pointer += 1;
                             }
                         }
                         '!' => {
-                            if self.peek_one_char() == '=' {
+                            if self.peek_one_char() == Some('=') {
                                 next_token = Some(Token {
                                     token_type: TokenType::BangEqual,
                                     lexeme: "!=".to_string(),
-                                    line: line,
+                                    line,
+                                    column,
+                                    literal: None,
                                 });
                                 self.pointer += 2;
                             } else {
                                 next_token = Some(Token {
                                     token_type: TokenType::Bang,
-                                    lexeme: lexeme,
-                                    line: line,
+                                    lexeme,
+                                    line,
+                                    column,
+                                    literal: None,
                                 });
                                 self.pointer += 1;
                             }
                         }
                         '/' => {
-                            if self.peek_one_char() == '/' {
+                            if self.peek_one_char() == Some('/') {
+                                let mut comment = String::new();
                                 while let Some(current_char) = self.chars.get(self.pointer) {
                                     if current_char == &'\n' {
                                         break;
                                     }
+                                    comment.push(*current_char);
                                     self.pointer += 1;
                                 }
+                                if !self.keep_comments {
+                                    continue;
+                                }
+                                next_token = Some(Token {
+                                    token_type: TokenType::Comment,
+                                    lexeme: comment.trim_end().to_string(),
+                                    line,
+                                    column,
+                                    literal: None,
+                                });
+                            } else if self.peek_one_char() == Some('*') {
+                                match self.block_comment(line, column) {
+                                    Some(token) => next_token = Some(token),
+                                    None => continue,
+                                }
                             } else {
                                 next_token = Some(Token {
                                     token_type: TokenType::Slash,
-                                    lexeme: lexeme,
-                                    line: line,
+                                    lexeme,
+                                    line,
+                                    column,
+                                    literal: None,
                                 });
                                 self.pointer += 1;
                             }
                         }
                         '"' => {
+                            next_token = Some(self.string(line, column));
+                        }
+                        '\n' if self.keep_whitespace => {
+                            next_token = Some(Token {
+                                token_type: TokenType::Newline,
+                                lexeme,
+                                line,
+                                column,
+                                literal: None,
+                            });
+                            self.current_line += 1;
                             self.pointer += 1;
-                            let mut string_lexeme = String::from("\"");
-                            let mut current_char: Option<&char> = self.chars.get(self.pointer);
-
-                            loop {
-                                match current_char {
-                                    Some(c) => {
-                                        string_lexeme.push(c.clone());
-                                        if c == &'"' {
-                                            break;
-                                        }
-                                        self.pointer += 1;
-                                        current_char = self.chars.get(self.pointer);
-                                    }
-                                    None => return None,
+                            self.line_start = self.pointer;
+                        }
+                        ' ' | '\t' | '\r' if self.keep_whitespace => {
+                            let mut whitespace = String::new();
+                            while let Some(c) = self.chars.get(self.pointer) {
+                                if c != &' ' && c != &'\t' && c != &'\r' {
+                                    break;
                                 }
+                                whitespace.push(*c);
+                                self.pointer += 1;
                             }
                             next_token = Some(Token {
-                                token_type: TokenType::String,
-                                lexeme: string_lexeme,
-                                line: line,
+                                token_type: TokenType::Whitespace,
+                                lexeme: whitespace,
+                                line,
+                                column,
+                                literal: None,
                             });
-                            self.pointer += 1;
                         }
-                        ' ' | '\t' | '\n' => {
+                        ' ' | '\t' | '\r' | '\n' => {
                             while let Some(c) = self.chars.get(self.pointer) {
                                 if c == &'\n' {
                                     self.current_line += 1;
-                                } else if c != &' ' && c != &'\t' {
+                                    self.line_start = self.pointer + 1;
+                                } else if c != &' ' && c != &'\t' && c != &'\r' {
                                     break;
                                 }
                                 self.pointer += 1;
                             }
                             continue;
                         }
-                        other => {
-                            // At this point, its either an identifier, a digit or a keyword
-                            let mut current_char: Option<&char> = Some(other);
+                        other if other.is_ascii_digit() => {
                             let mut word = String::new();
-                            loop {
-                                match current_char {
-                                    Some(c) => {
-                                        if c == &' ' || c == &'\n' || c == &'\t' {
-                                            break;
-                                        }
-                                        self.pointer += 1;
-                                        word.push(c.clone());
-                                        current_char = self.chars.get(self.pointer);
+                            while let Some(c) = self.chars.get(self.pointer) {
+                                if !c.is_ascii_digit() {
+                                    break;
+                                }
+                                word.push(*c);
+                                self.pointer += 1;
+                            }
+
+                            // A fractional part needs at least one digit after the dot, so
+                            // "123." lexes as a number followed by a Dot.
+                            if self.chars.get(self.pointer) == Some(&'.')
+                                && self
+                                    .chars
+                                    .get(self.pointer + 1)
+                                    .is_some_and(|c| c.is_ascii_digit())
+                            {
+                                word.push('.');
+                                self.pointer += 1;
+                                while let Some(c) = self.chars.get(self.pointer) {
+                                    if !c.is_ascii_digit() {
+                                        break;
                                     }
-                                    None => return None,
+                                    word.push(*c);
+                                    self.pointer += 1;
                                 }
                             }
 
-                            if word.parse::<f64>().is_ok() {
-                                next_token = Some(Token {
-                                    token_type: TokenType::Number,
-                                    lexeme: word,
-                                    line: line,
-                                });
-                            } else if self.keyword_list.contains(&word) {
-                                next_token = Some(Token {
-                                    token_type: TokenType::Keyword,
-                                    lexeme: word,
-                                    line: line,
-                                });
-                            } else {
-                                next_token = Some(Token {
-                                    token_type: TokenType::Identifier,
-                                    lexeme: word,
-                                    line: line,
-                                });
+                            next_token = Some(Token {
+                                token_type: TokenType::Number,
+                                lexeme: word,
+                                line,
+                                column,
+                                literal: None,
+                            });
+                        }
+                        other if other.is_alphabetic() || other == &'_' => {
+                            // At this point, its either an identifier or a keyword
+                            let mut word = String::new();
+                            while let Some(c) = self.chars.get(self.pointer) {
+                                if !c.is_alphanumeric() && c != &'_' {
+                                    break;
+                                }
+                                word.push(*c);
+                                self.pointer += 1;
                             }
+
+                            let token_type = match self.keyword_list.get(&word) {
+                                Some(keyword) => *keyword,
+                                // `from` is only a keyword where it starts an import, so it
+                                // still works as a name everywhere else.
+                                None if word == "from" && self.string_follows() => TokenType::From,
+                                None => TokenType::Identifier,
+                            };
+                            next_token = Some(Token {
+                                token_type,
+                                lexeme: word,
+                                line,
+                                column,
+                                literal: None,
+                            });
+                        }
+                        _ => {
+                            next_token = Some(Token {
+                                token_type: TokenType::Error,
+                                lexeme: "Unexpected character.".to_string(),
+                                line,
+                                column,
+                                literal: None,
+                            });
+                            self.pointer += 1;
                         }
                     }
                 }
@@ -345,61 +574,78 @@ impl Iterator for Lexer {
 
 impl std::fmt::Display for Token {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        let ttype: &str;
-        match self.token_type {
+        let ttype: &str = match self.token_type {
             // Single-character tokens.
-            TokenType::LeftParen => ttype = "LeftParen",
-            TokenType::RightParen => ttype = "RightParen",
-            TokenType::LeftBrace => ttype = "LeftBrace",
-            TokenType::RightBrace => ttype = "RightBrace",
-            TokenType::Comma => ttype = "Comma",
-            TokenType::Dot => ttype = "Dot",
-            TokenType::Minus => ttype = "Minus",
-            TokenType::Plus => ttype = "Plus",
-            TokenType::Semicolon => ttype = "Semicolon",
-            TokenType::Slash => ttype = "Slash",
-            TokenType::Star => ttype = "Star",
-
-            TokenType::Bang => ttype = "Bang",
-            TokenType::BangEqual => ttype = "BangEqual",
-            TokenType::Equal => ttype = "Equal",
-            TokenType::EqualEqual => ttype = "EqualEqual",
-            TokenType::Greater => ttype = "Greater",
-            TokenType::GreaterEqual => ttype = "GreaterEqual",
-            TokenType::Less => ttype = "Less",
-            TokenType::LessEqual => ttype = "LessEqual",
-
-            TokenType::Identifier => ttype = "Identifier",
-            TokenType::String => ttype = "String",
-            TokenType::Number => ttype = "Number",
+            TokenType::LeftParen => "LeftParen",
+            TokenType::RightParen => "RightParen",
+            TokenType::LeftBrace => "LeftBrace",
+            TokenType::RightBrace => "RightBrace",
+            TokenType::LeftBracket => "LeftBracket",
+            TokenType::RightBracket => "RightBracket",
+            TokenType::Comma => "Comma",
+            TokenType::Colon => "Colon",
+            TokenType::Dot => "Dot",
+            TokenType::Minus => "Minus",
+            TokenType::Plus => "Plus",
+            TokenType::Semicolon => "Semicolon",
+            TokenType::Slash => "Slash",
+            TokenType::Star => "Star",
+            TokenType::Percent => "Percent",
+
+            TokenType::Bang => "Bang",
+            TokenType::BangEqual => "BangEqual",
+            TokenType::Equal => "Equal",
+            TokenType::EqualEqual => "EqualEqual",
+            TokenType::Greater => "Greater",
+            TokenType::GreaterEqual => "GreaterEqual",
+            TokenType::Less => "Less",
+            TokenType::LessEqual => "LessEqual",
+
+            TokenType::Identifier => "Identifier",
+            TokenType::String => "String",
+            TokenType::Interpolation => "Interpolation",
+            TokenType::Number => "Number",
 
             // Keywords.
-            TokenType::And => ttype = "And",
-            TokenType::Class => ttype = "Class",
-            TokenType::Else => ttype = "Else",
-            TokenType::False => ttype = "False",
-            TokenType::Fun => ttype = "Fun",
-            TokenType::For => ttype = "For",
-            TokenType::If => ttype = "If",
-            TokenType::Nil => ttype = "Nil",
-            TokenType::Or => ttype = "Or",
-            TokenType::Print => ttype = "Print",
-            TokenType::Return => ttype = "Return",
-            TokenType::Super => ttype = "Super",
-            TokenType::This => ttype = "This",
-            TokenType::True => ttype = "True",
-            TokenType::Var => ttype = "Var",
-            TokenType::While => ttype = "While",
-            TokenType::Keyword => ttype = "Keyword",
+            TokenType::And => "And",
+            TokenType::Break => "Break",
+            TokenType::Catch => "Catch",
+            TokenType::Class => "Class",
+            TokenType::Continue => "Continue",
+            TokenType::Else => "Else",
+            TokenType::False => "False",
+            TokenType::Finally => "Finally",
+            TokenType::From => "From",
+            TokenType::Fun => "Fun",
+            TokenType::For => "For",
+            TokenType::If => "If",
+            TokenType::Import => "Import",
+            TokenType::In => "In",
+            TokenType::Nil => "Nil",
+            TokenType::Or => "Or",
+            TokenType::Print => "Print",
+            TokenType::Return => "Return",
+            TokenType::Super => "Super",
+            TokenType::This => "This",
+            TokenType::Throw => "Throw",
+            TokenType::True => "True",
+            TokenType::Try => "Try",
+            TokenType::Var => "Var",
+            TokenType::While => "While",
+
+            TokenType::Comment => "Comment",
+            TokenType::Whitespace => "Whitespace",
+            TokenType::Newline => "Newline",
+            TokenType::Eof => "Eof",
 
             // Error.
-            TokenType::Error => ttype = "Error",
-        }
-        println!(
+            TokenType::Error => "Error",
+        };
+        write!(
+            f,
             "Type: {} | Line: {} | Lexeme: {}",
             ttype, self.line, self.lexeme
-        );
-        Ok(())
+        )
     }
 }
 
@@ -409,37 +655,244 @@ impl Lexer {
         let mut source_buffer: String = String::new();
         file.read_to_string(&mut source_buffer)?;
 
-        let chars: Vec<char> = source_buffer.chars().collect();
+        Ok(Lexer::from_source(&source_buffer))
+    }
+
+    pub fn from_source(source: &str) -> Lexer {
+        let chars: Vec<char> = source.chars().collect();
 
-        Ok(Lexer {
-            chars: chars,
+        Lexer {
+            chars,
             pointer: 0,
-            current_line: 0,
-            keyword_list: vec![
-                "and".to_string(),
-                "class".to_string(),
-                "else".to_string(),
-                "false".to_string(),
-                "for".to_string(),
-                "fun".to_string(),
-                "if".to_string(),
-                "nil".to_string(),
-                "or".to_string(),
-                "return".to_string(),
-                "super".to_string(),
-                "this".to_string(),
-                "true".to_string(),
-                "var".to_string(),
-                "while".to_string(),
-                "print".to_string(),
-            ],
+            current_line: 1,
+            line_start: 0,
+            keyword_list: HashMap::from([
+                ("and".to_string(), TokenType::And),
+                ("break".to_string(), TokenType::Break),
+                ("catch".to_string(), TokenType::Catch),
+                ("class".to_string(), TokenType::Class),
+                ("continue".to_string(), TokenType::Continue),
+                ("else".to_string(), TokenType::Else),
+                ("false".to_string(), TokenType::False),
+                ("finally".to_string(), TokenType::Finally),
+                ("for".to_string(), TokenType::For),
+                ("fun".to_string(), TokenType::Fun),
+                ("if".to_string(), TokenType::If),
+                ("import".to_string(), TokenType::Import),
+                ("in".to_string(), TokenType::In),
+                ("nil".to_string(), TokenType::Nil),
+                ("or".to_string(), TokenType::Or),
+                ("return".to_string(), TokenType::Return),
+                ("super".to_string(), TokenType::Super),
+                ("this".to_string(), TokenType::This),
+                ("throw".to_string(), TokenType::Throw),
+                ("try".to_string(), TokenType::Try),
+                ("true".to_string(), TokenType::True),
+                ("var".to_string(), TokenType::Var),
+                ("while".to_string(), TokenType::While),
+                ("print".to_string(), TokenType::Print),
+            ]),
+            keep_comments: false,
+            keep_whitespace: false,
+            pending: None,
+            interpolations: Vec::new(),
+        }
+    }
+
+    /// Makes the lexer emit comments as `Comment` tokens instead of skipping them. The
+    /// parser doesn't accept these, this is for tools that work on the token stream.
+    pub fn with_comments(mut self) -> Lexer {
+        self.keep_comments = true;
+        self
+    }
+
+    /// Makes the lexer emit all trivia, comments as well as whitespace and newlines, so that
+    /// the lexemes of the tokens it produces add up to the source text again. Error tokens
+    /// still carry their message, `offset` gives their extent in the source.
+    pub fn with_trivia(mut self) -> Lexer {
+        self.keep_comments = true;
+        self.keep_whitespace = true;
+        self
+    }
+
+    /// The position, in chars, of the next character the lexer will read.
+    pub fn offset(&self) -> usize {
+        self.pointer
+    }
+
+    // Lexes a `/* */` comment, which may nest and span lines. Returns None when comments are
+    // skipped, but an unterminated comment is always reported, from where it starts.
+    fn block_comment(&mut self, line: usize, column: usize) -> Option<Token> {
+        let start = self.pointer;
+        self.pointer += 2;
+        let mut depth = 1;
+        while depth > 0 {
+            match (self.chars.get(self.pointer), self.peek_one_char()) {
+                (Some('/'), Some('*')) => {
+                    depth += 1;
+                    self.pointer += 2;
+                }
+                (Some('*'), Some('/')) => {
+                    depth -= 1;
+                    self.pointer += 2;
+                }
+                (Some('\n'), _) => {
+                    self.pointer += 1;
+                    self.current_line += 1;
+                    self.line_start = self.pointer;
+                }
+                (Some(_), _) => self.pointer += 1,
+                (None, _) => {
+                    return Some(Token {
+                        token_type: TokenType::Error,
+                        lexeme: "Unterminated block comment.".to_string(),
+                        line,
+                        column,
+                        literal: None,
+                    });
+                }
+            }
+        }
+        if !self.keep_comments {
+            return None;
+        }
+        Some(Token {
+            token_type: TokenType::Comment,
+            lexeme: self.chars[start..self.pointer].iter().collect(),
+            line,
+            column,
+            literal: None,
         })
     }
 
+    // Lexes a string literal from its opening quote, or the rest of an interpolated string
+    // from the `}` closing an interpolation. The first invalid escape sequence in it is
+    // returned as an error token, with the string token held back to follow it so that the
+    // parser doesn't report a missing expression as well.
+    fn string(&mut self, line: usize, column: usize) -> Token {
+        let start = self.pointer;
+        self.pointer += 1;
+        let mut literal = String::new();
+        let mut error = None;
+        let token_type = loop {
+            match self.chars.get(self.pointer) {
+                Some('"') => {
+                    self.pointer += 1;
+                    break TokenType::String;
+                }
+                Some('$') if self.chars.get(self.pointer + 1) == Some(&'{') => {
+                    self.pointer += 2;
+                    self.interpolations.push(0);
+                    break TokenType::Interpolation;
+                }
+                Some('\\') => {
+                    let escape_line = self.current_line;
+                    let escape_column = self.pointer - self.line_start + 1;
+                    match self.escape() {
+                        Ok(c) => literal.push(c),
+                        Err(message) => {
+                            error.get_or_insert(Token {
+                                token_type: TokenType::Error,
+                                lexeme: message.to_string(),
+                                line: escape_line,
+                                column: escape_column,
+                                literal: None,
+                            });
+                        }
+                    }
+                }
+                Some(c) => {
+                    literal.push(*c);
+                    self.pointer += 1;
+                    // Strings may span lines.
+                    if *c == '\n' {
+                        self.current_line += 1;
+                        self.line_start = self.pointer;
+                    }
+                }
+                None => {
+                    return Token {
+                        token_type: TokenType::Error,
+                        lexeme: "Unterminated string.".to_string(),
+                        line,
+                        column,
+                        literal: None,
+                    };
+                }
+            }
+        };
+
+        let token = Token {
+            token_type,
+            lexeme: self.chars[start..self.pointer].iter().collect(),
+            line,
+            column,
+            literal: Some(literal),
+        };
+        match error {
+            Some(error) => {
+                self.pending = Some(token);
+                error
+            }
+            None => token,
+        }
+    }
+
+    // Decodes the escape sequence whose backslash is at the pointer and moves past it. An
+    // invalid one only has its backslash skipped, the rest is read as ordinary characters.
+    fn escape(&mut self) -> Result<char, &'static str> {
+        self.pointer += 1;
+        let decoded = match self.chars.get(self.pointer) {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('$') => '$',
+            Some('u') => return self.unicode_escape(),
+            _ => return Err("Invalid escape sequence."),
+        };
+        self.pointer += 1;
+        Ok(decoded)
+    }
+
+    // Decodes the `u{...}` part of a unicode escape, one to six hex digits naming a scalar
+    // value.
+    fn unicode_escape(&mut self) -> Result<char, &'static str> {
+        const INVALID: &str = "Invalid unicode escape sequence.";
+        if self.chars.get(self.pointer + 1) != Some(&'{') {
+            return Err(INVALID);
+        }
+        let digits_start = self.pointer + 2;
+        let mut end = digits_start;
+        while self.chars.get(end).is_some_and(|c| c.is_ascii_hexdigit()) {
+            end += 1;
+        }
+        if self.chars.get(end) != Some(&'}') {
+            return Err(INVALID);
+        }
+        self.pointer = end + 1;
+
+        let digits: String = self.chars[digits_start..end].iter().collect();
+        if digits.is_empty() || digits.len() > 6 {
+            return Err(INVALID);
+        }
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or(INVALID)
+    }
+
+    // Whether the next char other than whitespace opens a string.
+    fn string_follows(&self) -> bool {
+        self.chars[self.pointer..]
+            .iter()
+            .find(|c| !matches!(c, ' ' | '\t' | '\r' | '\n'))
+            == Some(&'"')
+    }
+
     // This is actually small enough to not be a function anymore
-    fn peek_one_char(&self) -> char {
-        // Peeks the next char.
-        // TODO: self.chars[p] can go out of bounds and panic. Handle cleanly.
-        return self.chars[self.pointer + 1];
+    fn peek_one_char(&self) -> Option<char> {
+        // Peeks the next char, None at the end of the source.
+        self.chars.get(self.pointer + 1).copied()
     }
 }
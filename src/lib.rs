@@ -1,2 +1,23 @@
+pub mod conformance;
+pub mod cst;
+pub mod environment;
+pub mod formatter;
+pub mod highlighter;
+pub mod interpreter;
+pub mod json;
 pub mod lexer;
+pub mod linter;
+pub mod lsp;
+pub mod natives;
+pub mod optimizer;
 pub mod parser;
+pub mod printer;
+pub mod resolver;
+pub mod symbols;
+pub mod value;
+
+pub use interpreter::CapturedOutput;
+pub use interpreter::Interpreter;
+pub use lexer::RoxError;
+pub use value::NativeFunction;
+pub use value::Value;
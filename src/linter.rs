@@ -0,0 +1,622 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::interpreter::Interpreter;
+use crate::lexer::Lexer;
+use crate::lexer::Token;
+use crate::lexer::TokenType;
+use crate::parser::CompileError;
+use crate::parser::Expr;
+use crate::parser::FunctionDecl;
+use crate::parser::LiteralValue;
+use crate::parser::Parser;
+use crate::parser::Stmt;
+use crate::printer;
+use crate::resolver::Resolver;
+
+/// A check the linter can run. Each rule has a stable code and a kebab-case name, and either
+/// can be used to configure it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    UnusedVariable,
+    UnreachableCode,
+    ShadowedVariable,
+    UndeclaredGlobal,
+    SelfComparison,
+    ConstantCondition,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 6] = [
+        Rule::UnusedVariable,
+        Rule::UnreachableCode,
+        Rule::ShadowedVariable,
+        Rule::UndeclaredGlobal,
+        Rule::SelfComparison,
+        Rule::ConstantCondition,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "L001",
+            Rule::UnreachableCode => "L002",
+            Rule::ShadowedVariable => "L003",
+            Rule::UndeclaredGlobal => "L004",
+            Rule::SelfComparison => "L005",
+            Rule::ConstantCondition => "L006",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "unused-variable",
+            Rule::UnreachableCode => "unreachable-code",
+            Rule::ShadowedVariable => "shadowed-variable",
+            Rule::UndeclaredGlobal => "undeclared-global",
+            Rule::SelfComparison => "self-comparison",
+            Rule::ConstantCondition => "constant-condition",
+        }
+    }
+
+    /// Looks a rule up by its name or its code.
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL
+            .into_iter()
+            .find(|rule| rule.name() == name || rule.code().eq_ignore_ascii_case(name))
+    }
+
+    /// Assigning to a global that doesn't exist always fails at runtime, so it is an error.
+    /// Everything else might be intended and only warns.
+    pub fn default_severity(self) -> Severity {
+        match self {
+            Rule::UndeclaredGlobal => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The rule is turned off.
+    Allow,
+    Warning,
+    Error,
+}
+
+/// Which rules run and how seriously their findings are taken.
+#[derive(Debug, Clone)]
+pub struct LintOptions {
+    severities: HashMap<Rule, Severity>,
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        Self {
+            severities: Rule::ALL
+                .into_iter()
+                .map(|rule| (rule, rule.default_severity()))
+                .collect(),
+        }
+    }
+}
+
+impl LintOptions {
+    pub fn set(&mut self, rule: Rule, severity: Severity) {
+        self.severities.insert(rule, severity);
+    }
+
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.severities[&rule]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning | Severity::Allow => "warning",
+        };
+        write!(
+            f,
+            "[line {}] {} {} ({}): {}",
+            self.line,
+            severity,
+            self.rule.code(),
+            self.rule.name(),
+            self.message
+        )
+    }
+}
+
+/// Lints a script, returning its findings in source order. Source that doesn't compile is
+/// not linted and its errors are returned instead.
+///
+/// A `// rox-allow: rule, ...` comment turns the listed rules off for its own line and the
+/// line after it, so it can either trail the offending code or sit just above it. A block
+/// comment works the same way for every line it spans and the one after.
+pub fn lint_source(
+    source: &str,
+    options: &LintOptions,
+) -> Result<Vec<Diagnostic>, Vec<CompileError>> {
+    let mut lexer = Lexer::from_source(source);
+    let statements = Parser::new(&mut lexer).parse()?;
+    Resolver::new().resolve(&statements)?;
+
+    let mut allowed = HashSet::new();
+    for token in Lexer::from_source(source).with_comments() {
+        if token.token_type != TokenType::Comment {
+            continue;
+        }
+        let text = match token.lexeme.strip_prefix("/*") {
+            Some(block) => block.strip_suffix("*/").unwrap_or(block),
+            None => &token.lexeme[2..],
+        };
+        let Some(rules) = text.trim().strip_prefix("rox-allow:") else {
+            continue;
+        };
+        let end_line = token.line + token.lexeme.matches('\n').count();
+        for rule in rules
+            .split(',')
+            .filter_map(|name| Rule::from_name(name.trim()))
+        {
+            for line in token.line..=end_line + 1 {
+                allowed.insert((rule, line));
+            }
+        }
+    }
+
+    let mut diagnostics = lint(&statements, options);
+    diagnostics.retain(|d| !allowed.contains(&(d.rule, d.line)));
+    Ok(diagnostics)
+}
+
+/// Lints an already parsed and resolved program.
+pub fn lint(statements: &[Stmt], options: &LintOptions) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        options,
+        builtins: Interpreter::new(),
+        globals: HashMap::new(),
+        scopes: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    // Functions can refer to globals declared further down the script.
+    for statement in statements {
+        match statement {
+            Stmt::Var { name, .. } | Stmt::Class { name, .. } => linter.declare_global(name),
+            Stmt::Function { decl } => linter.declare_global(&decl.name),
+            Stmt::Import { alias, names, .. } => {
+                for name in alias.iter().chain(names) {
+                    linter.declare_global(name);
+                }
+            }
+            _ => {}
+        }
+    }
+    linter.statements(statements);
+
+    let mut diagnostics = linter.diagnostics;
+    diagnostics.sort_by_key(|d| d.line);
+    diagnostics
+}
+
+#[derive(Clone, Copy)]
+enum LocalKind {
+    Variable,
+    Parameter,
+    CatchVariable,
+    Function,
+    Class,
+}
+
+struct Local {
+    name: Token,
+    kind: LocalKind,
+    read: bool,
+}
+
+struct Linter<'a> {
+    options: &'a LintOptions,
+    // Only asked whether a name is one of the natives.
+    builtins: Interpreter,
+    // Every top level name, with the line it is declared on.
+    globals: HashMap<String, usize>,
+    // Local scopes, innermost last.
+    scopes: Vec<Vec<Local>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, rule: Rule, line: usize, message: String) {
+        let severity = self.options.severity(rule);
+        if severity != Severity::Allow {
+            self.diagnostics.push(Diagnostic {
+                rule,
+                severity,
+                line,
+                message,
+            });
+        }
+    }
+
+    fn declare_global(&mut self, name: &Token) {
+        self.globals.entry(name.lexeme.clone()).or_insert(name.line);
+    }
+
+    fn declare(&mut self, name: &Token, kind: LocalKind) {
+        let Some((scope, enclosing)) = self.scopes.split_last_mut() else {
+            return;
+        };
+        scope.push(Local {
+            name: name.clone(),
+            kind,
+            read: false,
+        });
+
+        let shadowed = enclosing
+            .iter()
+            .rev()
+            .flatten()
+            .find(|local| local.name.lexeme == name.lexeme)
+            .map(|local| local.name.line)
+            .or_else(|| self.globals.get(&name.lexeme).copied());
+        if let Some(line) = shadowed {
+            self.report(
+                Rule::ShadowedVariable,
+                name.line,
+                format!(
+                    "'{}' shadows the variable declared on line {}.",
+                    name.lexeme, line
+                ),
+            );
+        }
+    }
+
+    fn lookup(&mut self, name: &str) -> Option<&mut Local> {
+        self.scopes.iter_mut().rev().find_map(|scope| {
+            scope
+                .iter_mut()
+                .rev()
+                .find(|local| local.name.lexeme == name)
+        })
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap_or_default();
+        for local in scope {
+            // A leading underscore marks a name as deliberately unused.
+            if local.read || local.name.lexeme.starts_with('_') {
+                continue;
+            }
+            let what = match local.kind {
+                LocalKind::Variable => "Local variable",
+                LocalKind::Parameter => "Parameter",
+                LocalKind::CatchVariable => "Catch variable",
+                LocalKind::Function => "Local function",
+                LocalKind::Class => "Local class",
+            };
+            self.report(
+                Rule::UnusedVariable,
+                local.name.line,
+                format!("{} '{}' is never used.", what, local.name.lexeme),
+            );
+        }
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) {
+        let mut returned: Option<usize> = None;
+        for statement in statements {
+            if let Some(return_line) = returned.take() {
+                let line = statement_line(statement).unwrap_or(return_line);
+                self.report(Rule::UnreachableCode, line, "Unreachable code.".to_string());
+            }
+            self.statement(statement);
+            if returned.is_none()
+                && let Some(line) = always_returns(statement)
+            {
+                // Only the first unreachable statement is reported.
+                returned = Some(line);
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Expression { expr } | Stmt::Print { expr } => self.expr(expr),
+            Stmt::Var { name, initializer } => {
+                if let Some(initializer) = initializer {
+                    self.expr(initializer);
+                }
+                self.declare(name, LocalKind::Variable);
+            }
+            Stmt::Block { statements } => {
+                self.begin_scope();
+                self.statements(statements);
+                self.end_scope();
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.condition(condition, "if");
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => {
+                // `while (true)` is how an endless loop is written, and what `for (;;)` becomes.
+                let endless = matches!(
+                    condition,
+                    Expr::Literal {
+                        value: LiteralValue::Bool(true),
+                        ..
+                    }
+                );
+                if !endless {
+                    self.condition(condition, "while");
+                }
+                self.statement(body);
+                if let Some(increment) = increment {
+                    self.expr(increment);
+                }
+            }
+            Stmt::Break { .. } | Stmt::Continue { .. } => {}
+            Stmt::Throw { value, .. } => self.expr(value),
+            Stmt::Import { alias, names, .. } => {
+                for name in alias.iter().chain(names) {
+                    self.declare(name, LocalKind::Variable);
+                }
+            }
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.begin_scope();
+                self.statements(body);
+                self.end_scope();
+                if let Some((name, handler)) = catch {
+                    self.begin_scope();
+                    self.declare(name, LocalKind::CatchVariable);
+                    self.statements(handler);
+                    self.end_scope();
+                }
+                if let Some(finally) = finally {
+                    self.begin_scope();
+                    self.statements(finally);
+                    self.end_scope();
+                }
+            }
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => {
+                self.expr(iterable);
+                self.begin_scope();
+                self.declare(name, LocalKind::Variable);
+                self.statement(body);
+                self.end_scope();
+            }
+            Stmt::Function { decl } => {
+                self.declare(&decl.name, LocalKind::Function);
+                self.function(decl);
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                self.declare(name, LocalKind::Class);
+                if let Some(superclass) = superclass {
+                    self.expr(superclass);
+                }
+                for method in methods {
+                    self.function(method);
+                }
+            }
+        }
+    }
+
+    fn function(&mut self, decl: &FunctionDecl) {
+        self.begin_scope();
+        for param in &decl.params {
+            self.declare(param, LocalKind::Parameter);
+        }
+        self.statements(&decl.body);
+        self.end_scope();
+    }
+
+    fn condition(&mut self, condition: &Expr, keyword: &str) {
+        if is_constant(condition) {
+            self.report(
+                Rule::ConstantCondition,
+                condition.line().unwrap_or(0),
+                format!("The '{}' condition is always the same.", keyword),
+            );
+        }
+        self.expr(condition);
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Variable { name, .. } => {
+                if let Some(local) = self.lookup(&name.lexeme) {
+                    local.read = true;
+                }
+            }
+            Expr::Assign { name, value, .. } => {
+                self.expr(value);
+                let declared = self.lookup(&name.lexeme).is_some()
+                    || self.globals.contains_key(&name.lexeme)
+                    || self.builtins.get_global(&name.lexeme).is_some();
+                if !declared {
+                    self.report(
+                        Rule::UndeclaredGlobal,
+                        name.line,
+                        format!("Assignment to undeclared variable '{}'.", name.lexeme),
+                    );
+                }
+            }
+            Expr::Binary { left, op, right } => {
+                let comparison = matches!(
+                    op.token_type,
+                    TokenType::EqualEqual
+                        | TokenType::BangEqual
+                        | TokenType::Greater
+                        | TokenType::GreaterEqual
+                        | TokenType::Less
+                        | TokenType::LessEqual
+                );
+                if comparison && same_place(left, right) {
+                    self.report(
+                        Rule::SelfComparison,
+                        op.line,
+                        format!("'{}' is compared with itself.", printer::print_expr(left)),
+                    );
+                }
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Logical { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Unary { right, .. } => self.expr(right),
+            Expr::Grouping { expr } | Expr::Stringify { expr } => self.expr(expr),
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.expr(callee);
+                for argument in arguments {
+                    self.expr(argument);
+                }
+            }
+            Expr::Get { object, .. } => self.expr(object),
+            Expr::Set { object, value, .. } => {
+                self.expr(object);
+                self.expr(value);
+            }
+            Expr::List { elements, .. } => {
+                for element in elements {
+                    self.expr(element);
+                }
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            }
+            Expr::Index { object, index, .. } => {
+                self.expr(object);
+                self.expr(index);
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.expr(object);
+                self.expr(index);
+                self.expr(value);
+            }
+            Expr::Literal { .. } | Expr::This { .. } | Expr::Super { .. } => {}
+        }
+    }
+}
+
+// The line of the `return`, `break`, `continue` or `throw` that makes everything after the
+// statement unreachable, if it always jumps away.
+fn always_returns(statement: &Stmt) -> Option<usize> {
+    match statement {
+        Stmt::Return { keyword, .. }
+        | Stmt::Break { keyword }
+        | Stmt::Continue { keyword }
+        | Stmt::Throw { keyword, .. } => Some(keyword.line),
+        Stmt::Block { statements } => statements.iter().find_map(always_returns),
+        Stmt::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        } => always_returns(then_branch).and(always_returns(else_branch)),
+        _ => None,
+    }
+}
+
+fn statement_line(statement: &Stmt) -> Option<usize> {
+    match statement {
+        Stmt::Expression { expr } | Stmt::Print { expr } => expr.line(),
+        Stmt::Var { name, .. } | Stmt::Class { name, .. } | Stmt::ForIn { name, .. } => {
+            Some(name.line)
+        }
+        Stmt::Block { statements } => statements.first().and_then(statement_line),
+        Stmt::If { condition, .. } | Stmt::While { condition, .. } => condition.line(),
+        Stmt::Function { decl } => Some(decl.name.line),
+        Stmt::Return { keyword, .. }
+        | Stmt::Break { keyword }
+        | Stmt::Continue { keyword }
+        | Stmt::Throw { keyword, .. }
+        | Stmt::Import { keyword, .. } => Some(keyword.line),
+        Stmt::Try { body, .. } => body.first().and_then(statement_line),
+    }
+}
+
+// Whether evaluating the expression always gives the same value.
+fn is_constant(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal { .. } => true,
+        Expr::Grouping { expr } | Expr::Stringify { expr } | Expr::Unary { right: expr, .. } => {
+            is_constant(expr)
+        }
+        Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+            is_constant(left) && is_constant(right)
+        }
+        _ => false,
+    }
+}
+
+// Whether two expressions read the same variable or property, without side effects.
+fn same_place(left: &Expr, right: &Expr) -> bool {
+    match (left, right) {
+        (Expr::Grouping { expr }, other) | (other, Expr::Grouping { expr }) => {
+            same_place(expr, other)
+        }
+        (Expr::Variable { name: a, .. }, Expr::Variable { name: b, .. }) => a.lexeme == b.lexeme,
+        (Expr::This { .. }, Expr::This { .. }) => true,
+        (
+            Expr::Get {
+                object: a,
+                name: a_name,
+            },
+            Expr::Get {
+                object: b,
+                name: b_name,
+            },
+        ) => a_name.lexeme == b_name.lexeme && same_place(a, b),
+        _ => false,
+    }
+}
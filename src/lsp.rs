@@ -0,0 +1,455 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use crate::cst;
+use crate::cst::SyntaxKind;
+use crate::cst::SyntaxNode;
+use crate::json::Json;
+use crate::lexer::Lexer;
+use crate::lexer::TokenType;
+use crate::parser::CompileError;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::symbols::DeclarationKind;
+use crate::symbols::SymbolIndex;
+
+// JSON-RPC error codes.
+const PARSE_ERROR: f64 = -32700.0;
+const INVALID_REQUEST: f64 = -32600.0;
+const METHOD_NOT_FOUND: f64 = -32601.0;
+
+// LSP enumerations.
+const TEXT_DOCUMENT_SYNC_FULL: usize = 1;
+const SEVERITY_ERROR: usize = 1;
+const SYMBOL_CLASS: usize = 5;
+const SYMBOL_METHOD: usize = 6;
+const SYMBOL_FUNCTION: usize = 12;
+
+/// Runs a language server speaking LSP over `input` and `output` until the client sends
+/// `exit` or closes the input. Returns the exit code the process should use, which per the
+/// protocol is 0 only if the client asked for a shutdown first.
+///
+/// Documents are synced in full on every change. The server publishes the compile errors of
+/// a document whenever it changes and answers definition, references, hover and document
+/// symbol requests.
+pub fn run(mut input: impl BufRead, output: impl Write) -> io::Result<i32> {
+    let mut server = Server {
+        output,
+        documents: HashMap::new(),
+        shutdown: false,
+    };
+    while let Some(frame) = read_message(&mut input)? {
+        let message = match frame.and_then(|body| Json::parse(&body)) {
+            Ok(message) => message,
+            Err(error) => {
+                server.send_error(Json::Null, PARSE_ERROR, &error)?;
+                continue;
+            }
+        };
+        if message.get("method").and_then(Json::as_str) == Some("exit") {
+            break;
+        }
+        server.handle(&message)?;
+    }
+    Ok(if server.shutdown { 0 } else { 1 })
+}
+
+// Reads one message in the base protocol framing: headers, a blank line, then a body of
+// `Content-Length` bytes. None means the input has ended. A frame that can't be read gives
+// an error message to answer with, so one bad frame doesn't end the session.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Result<String, String>>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            content_length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let Some(content_length) = content_length else {
+        return Ok(Some(Err("Expect a Content-Length header.".to_string())));
+    };
+    let mut body = vec![0; content_length];
+    input.read_exact(&mut body)?;
+    Ok(Some(
+        String::from_utf8(body).map_err(|_| "Expect a UTF-8 message body.".to_string()),
+    ))
+}
+
+struct Server<W: Write> {
+    output: W,
+    documents: HashMap<String, String>,
+    shutdown: bool,
+}
+
+impl<W: Write> Server<W> {
+    fn send(&mut self, message: Json) -> io::Result<()> {
+        let body = message.to_string();
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+        self.output.flush()
+    }
+
+    fn send_error(&mut self, id: Json, code: f64, message: &str) -> io::Result<()> {
+        self.send(Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id),
+            (
+                "error",
+                Json::object([("code", code.into()), ("message", message.into())]),
+            ),
+        ]))
+    }
+
+    fn handle(&mut self, message: &Json) -> io::Result<()> {
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let Some(method) = message.get("method").and_then(Json::as_str) else {
+            // A response to a request we never send, or garbage.
+            if let Some(id) = message.get("id") {
+                self.send_error(id.clone(), INVALID_REQUEST, "Expect a method.")?;
+            }
+            return Ok(());
+        };
+
+        let Some(id) = message.get("id").cloned() else {
+            return self.notification(method, params);
+        };
+        let result = match method {
+            "initialize" => Some(capabilities()),
+            "shutdown" => {
+                self.shutdown = true;
+                Some(Json::Null)
+            }
+            "textDocument/definition" => Some(self.with_document(params, definition)),
+            "textDocument/references" => Some(self.with_document(params, references)),
+            "textDocument/hover" => Some(self.with_document(params, hover)),
+            "textDocument/documentSymbol" => Some(self.with_document(params, document_symbols)),
+            _ => None,
+        };
+        match result {
+            Some(result) => self.send(Json::object([
+                ("jsonrpc", "2.0".into()),
+                ("id", id),
+                ("result", result),
+            ])),
+            None => self.send_error(id, METHOD_NOT_FOUND, &format!("Unknown method {}.", method)),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) -> io::Result<()> {
+        let document = params.get("textDocument");
+        let Some(uri) = document.and_then(|d| d.get("uri")).and_then(Json::as_str) else {
+            return Ok(());
+        };
+        let uri = uri.to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let text = document.and_then(|d| d.get("text")).and_then(Json::as_str);
+                self.documents
+                    .insert(uri.clone(), text.unwrap_or_default().to_string());
+            }
+            "textDocument/didChange" => {
+                // With full sync the last change holds the whole new text.
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Json::as_array)
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(Json::as_str);
+                if let Some(text) = text {
+                    self.documents.insert(uri.clone(), text.to_string());
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+            }
+            _ => return Ok(()),
+        }
+        let diagnostics = self
+            .documents
+            .get(&uri)
+            .map_or_else(Vec::new, |text| diagnostics(text));
+        self.send(Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            (
+                "params",
+                Json::object([("uri", uri.into()), ("diagnostics", diagnostics.into())]),
+            ),
+        ]))
+    }
+
+    // Runs a request handler on the document named in its parameters. Requests for unknown
+    // documents get a null result.
+    fn with_document(&self, params: &Json, handler: fn(&Document, &Json) -> Json) -> Json {
+        let uri = params
+            .get("textDocument")
+            .and_then(|d| d.get("uri"))
+            .and_then(Json::as_str);
+        match uri.and_then(|uri| Some((uri, self.documents.get(uri)?))) {
+            Some((uri, text)) => handler(&Document::new(uri, text), params),
+            None => Json::Null,
+        }
+    }
+}
+
+fn capabilities() -> Json {
+    Json::object([
+        (
+            "capabilities",
+            Json::object([
+                ("textDocumentSync", TEXT_DOCUMENT_SYNC_FULL.into()),
+                ("definitionProvider", true.into()),
+                ("referencesProvider", true.into()),
+                ("hoverProvider", true.into()),
+                ("documentSymbolProvider", true.into()),
+            ]),
+        ),
+        ("serverInfo", Json::object([("name", "rox".into())])),
+    ])
+}
+
+// A document being worked on, with the analyses requests need.
+struct Document<'a> {
+    uri: &'a str,
+    text: &'a str,
+    lines: Vec<&'a str>,
+    // Empty when the document doesn't parse.
+    symbols: SymbolIndex,
+}
+
+impl<'a> Document<'a> {
+    fn new(uri: &'a str, text: &'a str) -> Document<'a> {
+        let mut lexer = Lexer::from_source(text);
+        let symbols = match Parser::new(&mut lexer).parse() {
+            Ok(statements) => SymbolIndex::build(&statements),
+            Err(_) => SymbolIndex::default(),
+        };
+        Document {
+            uri,
+            text,
+            lines: text.split('\n').collect(),
+            symbols,
+        }
+    }
+
+    fn position(&self, line: usize, column: usize) -> Json {
+        position(&self.lines, line, column)
+    }
+
+    fn parse_position(&self, position: &Json) -> Option<(usize, usize)> {
+        let line = position.get("line")?.as_f64()? as usize;
+        let character = position.get("character")?.as_f64()? as usize;
+        let text = self.lines.get(line)?;
+        let mut units = 0;
+        let mut column = 1;
+        for c in text.chars() {
+            if units >= character {
+                break;
+            }
+            units += c.len_utf16();
+            column += 1;
+        }
+        Some((line + 1, column))
+    }
+
+    fn range(&self, line: usize, start: usize, end: usize) -> Json {
+        range(&self.lines, line, start, end)
+    }
+
+    fn location(&self, line: usize, column: usize, length: usize) -> Json {
+        Json::object([
+            ("uri", self.uri.into()),
+            ("range", self.range(line, column, column + length)),
+        ])
+    }
+
+    fn declaration_at(&self, params: &Json) -> Option<usize> {
+        let (line, column) = self.parse_position(params.get("position")?)?;
+        self.symbols.declaration_at(line, column)
+    }
+}
+
+// An LSP position counts lines from 0 and characters in UTF-16 code units, ours count lines
+// and chars from 1.
+fn position(lines: &[&str], line: usize, column: usize) -> Json {
+    let text = lines.get(line.wrapping_sub(1)).copied().unwrap_or("");
+    let character: usize = text
+        .chars()
+        .take(column.saturating_sub(1))
+        .map(char::len_utf16)
+        .sum();
+    Json::object([
+        ("line", line.saturating_sub(1).into()),
+        ("character", character.into()),
+    ])
+}
+
+fn range(lines: &[&str], line: usize, start: usize, end: usize) -> Json {
+    Json::object([
+        ("start", position(lines, line, start)),
+        ("end", position(lines, line, end)),
+    ])
+}
+
+fn diagnostics(text: &str) -> Vec<Json> {
+    let mut lexer = Lexer::from_source(text);
+    let errors = match Parser::new(&mut lexer).parse() {
+        Ok(statements) => Resolver::new().resolve(&statements).err(),
+        Err(errors) => Some(errors),
+    };
+    let lines: Vec<&str> = text.split('\n').collect();
+    errors
+        .unwrap_or_default()
+        .iter()
+        .map(|error| diagnostic(&lines, error))
+        .collect()
+}
+
+fn diagnostic(lines: &[&str], error: &CompileError) -> Json {
+    // Errors at a token name it in their location, the others get an empty range.
+    let length = error
+        .location
+        .strip_prefix(" at '")
+        .and_then(|l| l.strip_suffix('\''))
+        .map_or(0, |lexeme| lexeme.chars().count());
+    Json::object([
+        (
+            "range",
+            range(lines, error.line, error.column, error.column + length),
+        ),
+        ("severity", SEVERITY_ERROR.into()),
+        ("source", "rox".into()),
+        ("message", error.message.as_str().into()),
+    ])
+}
+
+fn definition(document: &Document, params: &Json) -> Json {
+    let Some(declaration) = document.declaration_at(params) else {
+        return Json::Null;
+    };
+    let name = &document.symbols.declarations[declaration].name;
+    document.location(name.line, name.column, name.lexeme.chars().count())
+}
+
+fn references(document: &Document, params: &Json) -> Json {
+    let Some(declaration) = document.declaration_at(params) else {
+        return Json::Array(Vec::new());
+    };
+    let include_declaration = params
+        .get("context")
+        .and_then(|c| c.get("includeDeclaration"))
+        .and_then(Json::as_bool)
+        .unwrap_or(false);
+
+    let mut locations = Vec::new();
+    if include_declaration {
+        let name = &document.symbols.declarations[declaration].name;
+        locations.push(document.location(name.line, name.column, name.lexeme.chars().count()));
+    }
+    for token in document.symbols.references_to(declaration) {
+        locations.push(document.location(token.line, token.column, token.lexeme.chars().count()));
+    }
+    Json::Array(locations)
+}
+
+fn hover(document: &Document, params: &Json) -> Json {
+    let Some(declaration) = document.declaration_at(params) else {
+        return Json::Null;
+    };
+    let declaration = &document.symbols.declarations[declaration];
+    let scope = match declaration.kind {
+        DeclarationKind::Variable | DeclarationKind::Function | DeclarationKind::Class
+            if declaration.global =>
+        {
+            "global "
+        }
+        DeclarationKind::Variable | DeclarationKind::Function | DeclarationKind::Class => "local ",
+        DeclarationKind::Parameter | DeclarationKind::Method => "",
+    };
+    let kind = match declaration.kind {
+        DeclarationKind::Variable => "variable",
+        DeclarationKind::Parameter => "parameter",
+        DeclarationKind::Function => "function",
+        DeclarationKind::Class => "class",
+        DeclarationKind::Method => "method",
+    };
+    let value = format!(
+        "```lox\n{}\n```\n{}{} declared on line {}",
+        declaration.detail, scope, kind, declaration.name.line
+    );
+    Json::object([(
+        "contents",
+        Json::object([("kind", "markdown".into()), ("value", value.into())]),
+    )])
+}
+
+fn document_symbols(document: &Document, _params: &Json) -> Json {
+    Json::Array(symbols_in(document, &cst::parse(document.text)))
+}
+
+// The functions, classes and methods declared under a node, nested the way they are in the
+// source. Declarations missing their name are skipped.
+fn symbols_in(document: &Document, node: &SyntaxNode) -> Vec<Json> {
+    let mut symbols = Vec::new();
+    for child in node.child_nodes() {
+        let (kind, function) = match child.kind {
+            SyntaxKind::FunDecl => (SYMBOL_FUNCTION, child.child_nodes().next()),
+            SyntaxKind::Function if node.kind == SyntaxKind::ClassDecl => {
+                (SYMBOL_METHOD, Some(child))
+            }
+            SyntaxKind::ClassDecl => (SYMBOL_CLASS, Some(child)),
+            _ => {
+                symbols.extend(symbols_in(document, child));
+                continue;
+            }
+        };
+        let Some(function) = function else {
+            continue;
+        };
+        let Some(name) = function
+            .child_tokens()
+            .find(|t| t.kind == TokenType::Identifier)
+        else {
+            continue;
+        };
+        let tokens = child.tokens();
+        let (first, last) = (tokens[0], tokens[tokens.len() - 1]);
+        let range = Json::object([
+            ("start", document.position(first.line, first.column)),
+            (
+                "end",
+                document.position(last.line, last.column + last.text.chars().count()),
+            ),
+        ]);
+        let selection = document.range(
+            name.line,
+            name.column,
+            name.column + name.text.chars().count(),
+        );
+        let children = match child.kind {
+            SyntaxKind::ClassDecl => symbols_in(document, child),
+            _ => symbols_in(document, function),
+        };
+        symbols.push(Json::object([
+            ("name", name.text.as_str().into()),
+            ("kind", kind.into()),
+            ("range", range),
+            ("selectionRange", selection),
+            ("children", children.into()),
+        ]));
+    }
+    symbols
+}
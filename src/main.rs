@@ -1,16 +1,319 @@
+use rox::conformance;
+use rox::formatter;
+use rox::formatter::FormatOptions;
+use rox::highlighter;
+use rox::interpreter::INTERPRETER_STACK_SIZE;
+use rox::interpreter::Interpreter;
+use rox::interpreter::InterruptHandle;
+use rox::interpreter::Limits;
+use rox::interpreter::Permissions;
+use rox::lexer::EXIT_USAGE;
 use rox::lexer::Lexer;
 use rox::lexer::RoxError;
+use rox::linter;
+use rox::linter::LintOptions;
+use rox::linter::Rule;
+use rox::linter::Severity;
+use rox::lsp;
 
 use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+fn usage() -> ! {
+    eprintln!("Usage: rox run [options] [filepath] [args]");
+    eprintln!("       rox tokens [filepath]");
+    eprintln!("       rox test [directory]");
+    eprintln!("       rox fmt [options] [filepaths]");
+    eprintln!("       rox highlight [--html] [filepath]");
+    eprintln!("       rox lint [options] [filepaths]");
+    eprintln!("       rox lsp");
+    eprintln!();
+    eprintln!("Options for run:");
+    eprintln!("  --max-steps <n>     Stop after evaluating n expressions");
+    eprintln!("  --max-depth <n>     Maximum call depth before a stack overflow");
+    eprintln!("  --max-alloc <n>     Stop once the script has allocated n bytes, even if freed");
+    eprintln!("  --timeout <secs>    Interrupt the script after this many seconds");
+    eprintln!("  -O                  Fold constants and remove dead branches before running");
+    eprintln!("  --allow-fs          Let the script read and write files");
+    eprintln!("  --allow-env         Let the script read environment variables and its args");
+    eprintln!();
+    eprintln!("Options for fmt:");
+    eprintln!("  --check             Report unformatted files instead of rewriting them");
+    eprintln!("  --indent <n>        Spaces per indentation level (default 2)");
+    eprintln!("  --line-width <n>    Split argument lists on lines longer than n (default 80)");
+    eprintln!();
+    eprintln!("Options for lint:");
+    eprintln!("  -A, --allow <rule>  Turn a rule off");
+    eprintln!("  -W, --warn <rule>   Report a rule as a warning");
+    eprintln!("  -D, --deny <rule>   Report a rule as an error, failing the lint");
+    eprintln!();
+    eprintln!("Lint rules:");
+    for rule in Rule::ALL {
+        eprintln!("  {}  {}", rule.code(), rule.name());
+    }
+    std::process::exit(EXIT_USAGE);
+}
 
 fn main() -> Result<(), RoxError> {
     let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: rox [filepath]");
-        std::process::exit(-1);
+    if args.get(1).is_some_and(|command| command == "lsp") {
+        return language_server();
+    }
+    if args.len() < 3 {
+        usage();
+    }
+
+    match args[1].as_str() {
+        "run" => run(&args[2..]),
+        "tokens" => tokens(args[2].to_string()),
+        "test" => test(&args[2]),
+        "fmt" => fmt(&args[2..]),
+        "highlight" => highlight(&args[2..]),
+        "lint" => lint(&args[2..]),
+        _ => usage(),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: Option<&String>) -> T {
+    match value.and_then(|v| v.parse::<T>().ok()) {
+        Some(n) => n,
+        None => {
+            eprintln!("{} expects a non-negative number.", flag);
+            usage();
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), RoxError> {
+    let mut limits = Limits::for_stack_size(INTERPRETER_STACK_SIZE);
+    let mut timeout: Option<f64> = None;
+    let mut optimize = false;
+    let mut permissions = Permissions::default();
+    let mut source_file_path: Option<String> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-steps" => limits.max_steps = Some(parse_number(arg, args.next())),
+            "--max-depth" => limits.max_call_depth = parse_number(arg, args.next()),
+            "--max-alloc" => limits.max_allocated_bytes = Some(parse_number(arg, args.next())),
+            "--timeout" => timeout = Some(parse_number(arg, args.next())),
+            "-O" => optimize = true,
+            "--allow-fs" => permissions.fs = true,
+            "--allow-env" => permissions.env = true,
+            flag if flag.starts_with("--") => usage(),
+            path => {
+                source_file_path = Some(path.to_string());
+                // Everything after the script's path is for the script.
+                break;
+            }
+        }
+    }
+    let script_args: Vec<String> = args.cloned().collect();
+    let Some(source_file_path) = source_file_path else {
+        usage();
+    };
+
+    let source = fs::read_to_string(&source_file_path).map_err(RoxError::IOError)?;
+
+    let interrupt = InterruptHandle::new();
+    if let Some(seconds) = timeout {
+        let Ok(duration) = Duration::try_from_secs_f64(seconds) else {
+            eprintln!("--timeout expects a non-negative number.");
+            usage();
+        };
+        let interrupt = interrupt.clone();
+        thread::spawn(move || {
+            thread::sleep(duration);
+            interrupt.interrupt();
+        });
+    }
+
+    let interpreter_thread = thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(move || {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_limits(limits);
+            interpreter.set_interrupt_handle(interrupt);
+            interpreter.set_optimize(optimize);
+            interpreter.set_permissions(permissions);
+            interpreter.set_args(script_args);
+            interpreter.set_script_path(Path::new(&source_file_path));
+            interpreter.eval(&source).map(|_| ())
+        })
+        .map_err(RoxError::IOError)?;
+    let result = interpreter_thread
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(error @ RoxError::Exit(_)) => std::process::exit(error.exit_code()),
+        Err(error @ (RoxError::CompileError(_) | RoxError::RuntimeError(_))) => {
+            eprintln!("{}", error);
+            std::process::exit(error.exit_code());
+        }
+        Err(other) => Err(other),
+    }
+}
+
+fn test(dir: &str) -> Result<(), RoxError> {
+    let results = conformance::run_dir(Path::new(dir)).map_err(RoxError::IOError)?;
+
+    let mut failed = 0;
+    for result in &results {
+        if result.passed() {
+            continue;
+        }
+        failed += 1;
+        println!("FAIL {}", result.path.display());
+        for failure in &result.failures {
+            println!("  {}", failure);
+        }
+    }
+    println!("{} passed, {} failed.", results.len() - failed, failed);
+
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn fmt(args: &[String]) -> Result<(), RoxError> {
+    let mut options = FormatOptions::default();
+    let mut check = false;
+    let mut paths = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--indent" => options.indent_width = parse_number(arg, args.next()),
+            "--line-width" => options.line_width = parse_number(arg, args.next()),
+            flag if flag.starts_with("--") => usage(),
+            path => paths.push(path.to_string()),
+        }
+    }
+    if paths.is_empty() {
+        usage();
+    }
+
+    let mut unformatted = 0;
+    // A file that doesn't parse is reported and skipped, the rest still get formatted.
+    let mut failed_exit_code = None;
+    for path in &paths {
+        let source = fs::read_to_string(path).map_err(RoxError::IOError)?;
+        let formatted = match formatter::format_source(&source, &options) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                let error = RoxError::CompileError(errors);
+                eprintln!("{}:\n{}", path, error);
+                failed_exit_code.get_or_insert(error.exit_code());
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("Would reformat {}", path);
+            unformatted += 1;
+        } else {
+            fs::write(path, formatted).map_err(RoxError::IOError)?;
+        }
+    }
+
+    if let Some(code) = failed_exit_code {
+        std::process::exit(code);
+    }
+    if unformatted > 0 {
+        std::process::exit(1);
     }
-    let source_file_path: String = args[1].to_string();
+    Ok(())
+}
+
+fn highlight(args: &[String]) -> Result<(), RoxError> {
+    let (html, path) = match args {
+        [flag, path] if flag == "--html" => (true, path),
+        [path] if !path.starts_with("--") => (false, path),
+        _ => usage(),
+    };
+    let source = fs::read_to_string(path).map_err(RoxError::IOError)?;
+    if html {
+        print!("{}", highlighter::to_html(&source, path));
+    } else {
+        print!("{}", highlighter::to_ansi(&source));
+    }
+    Ok(())
+}
+
+fn lint(args: &[String]) -> Result<(), RoxError> {
+    let mut options = LintOptions::default();
+    let mut paths = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let severity = match arg.as_str() {
+            "-A" | "--allow" => Severity::Allow,
+            "-W" | "--warn" => Severity::Warning,
+            "-D" | "--deny" => Severity::Error,
+            flag if flag.starts_with('-') => usage(),
+            path => {
+                paths.push(path.to_string());
+                continue;
+            }
+        };
+        let Some(rule) = args.next().and_then(|name| Rule::from_name(name)) else {
+            eprintln!("{} expects the name or code of a lint rule.", arg);
+            usage();
+        };
+        options.set(rule, severity);
+    }
+    if paths.is_empty() {
+        usage();
+    }
+
+    let mut failed = false;
+    for path in &paths {
+        let source = fs::read_to_string(path).map_err(RoxError::IOError)?;
+        let diagnostics = match linter::lint_source(&source, &options) {
+            Ok(diagnostics) => diagnostics,
+            Err(errors) => {
+                let error = RoxError::CompileError(errors);
+                eprintln!("{}:\n{}", path, error);
+                std::process::exit(error.exit_code());
+            }
+        };
+        for diagnostic in &diagnostics {
+            println!("{}: {}", path, diagnostic);
+            failed |= diagnostic.severity == Severity::Error;
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn language_server() -> Result<(), RoxError> {
+    // Parsing recurses as deeply as the interpreter does, so it gets the same stack.
+    let server = thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(|| lsp::run(io::stdin().lock(), io::stdout().lock()))
+        .map_err(RoxError::IOError)?;
+    let code = server
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        .map_err(RoxError::IOError)?;
+    std::process::exit(code);
+}
 
+fn tokens(source_file_path: String) -> Result<(), RoxError> {
     let lexer = Lexer::new(source_file_path).map_err(RoxError::IOError)?;
 
     let mut token_len = 0;
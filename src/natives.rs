@@ -0,0 +1,594 @@
+use std::cell::RefCell;
+use std::env;
+use std::f64::consts;
+use std::fs;
+use std::io::{self, BufRead};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::environment::Environment;
+use crate::interpreter::Interpreter;
+use crate::interpreter::Unwind;
+use crate::value::LoxMap;
+use crate::value::LoxModule;
+use crate::value::LoxRange;
+use crate::value::MapKey;
+use crate::value::NativeFunction;
+use crate::value::Value;
+
+/// Defines the built-in functions every script starts with.
+pub fn define_globals(interpreter: &mut Interpreter) {
+    // Seconds since the unix epoch, for timing scripts.
+    interpreter.define_native(NativeFunction::new("clock", 0, |_, _| {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| Unwind::error("System clock is before the unix epoch."))?;
+        Ok(Value::Number(now.as_secs_f64()))
+    }));
+
+    interpreter.define_native(NativeFunction::new("str", 1, |interpreter, args| {
+        let string = args[0].to_string();
+        interpreter.allocate(string.len())?;
+        Ok(Value::String(Rc::from(string)))
+    }));
+
+    // Parses a string into a number, returning nil when it isn't one.
+    interpreter.define_native(NativeFunction::new("num", 1, |_, args| match &args[0] {
+        Value::Number(n) => Ok(Value::Number(*n)),
+        Value::String(s) => Ok(match s.trim().parse::<f64>() {
+            Ok(n) => Value::Number(n),
+            Err(_) => Value::Nil,
+        }),
+        other => Err(Unwind::error(&format!(
+            "Can't convert {} to a number.",
+            other.type_name()
+        ))),
+    }));
+
+    interpreter.define_native(NativeFunction::new("type", 1, |_, args| {
+        Ok(Value::String(Rc::from(args[0].type_name())))
+    }));
+
+    interpreter.define_native(NativeFunction::new("len", 1, |_, args| match &args[0] {
+        Value::String(s) => Ok(Value::Number(s.chars().count() as f64)),
+        Value::List(list) => Ok(Value::Number(list.borrow().len() as f64)),
+        Value::Map(map) => Ok(Value::Number(map.borrow().len() as f64)),
+        Value::Range(range) => Ok(Value::Number(range.len() as f64)),
+        other => Err(Unwind::error(&format!(
+            "Can't take the length of {}.",
+            other.type_name()
+        ))),
+    }));
+
+    interpreter.define_native(NativeFunction::new("range", 2, |_, args| {
+        match (&args[0], &args[1]) {
+            (Value::Number(start), Value::Number(end)) => Ok(Value::Range(LoxRange {
+                start: *start,
+                end: *end,
+            })),
+            _ => Err(Unwind::error("Range bounds must be numbers.")),
+        }
+    }));
+
+    // Reads one line from stdin without its line ending, or nil at end of input.
+    interpreter.define_native(NativeFunction::new("input", 0, |interpreter, _| {
+        let mut line = String::new();
+        let read = io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .map_err(|e| Unwind::error(&format!("Could not read input: {}.", e)))?;
+        if read == 0 {
+            return Ok(Value::Nil);
+        }
+        let trimmed = line.trim_end_matches(['\n', '\r']);
+        interpreter.allocate(trimmed.len())?;
+        Ok(Value::String(Rc::from(trimmed)))
+    }));
+
+    interpreter.define_native(NativeFunction::new("println", 1, |interpreter, args| {
+        interpreter.write_line(&args[0].to_string())?;
+        Ok(Value::Nil)
+    }));
+
+    interpreter.define_native(NativeFunction::new("exit", 1, |_, args| match &args[0] {
+        Value::Number(code) if code.fract() == 0.0 => Err(Unwind::Exit(*code as i32)),
+        _ => Err(Unwind::error("Exit code must be an integer.")),
+    }));
+
+    interpreter.set_global("math", Value::Module(Rc::new(math_module())));
+
+    interpreter.define_native(NativeFunction::new("readFile", 1, |interpreter, args| {
+        allow_fs(interpreter)?;
+        let path = string_argument("readFile", &args[0])?;
+        let contents = fs::read_to_string(path)
+            .map_err(|e| Unwind::error(&format!("Could not read file \"{}\": {}.", path, e)))?;
+        new_string(interpreter, contents)
+    }));
+
+    // Replaces the file's contents, creating it if needed.
+    interpreter.define_native(NativeFunction::new("writeFile", 2, |interpreter, args| {
+        allow_fs(interpreter)?;
+        let path = string_argument("writeFile", &args[0])?;
+        let contents = string_argument("writeFile", &args[1])?;
+        fs::write(path, contents)
+            .map_err(|e| Unwind::error(&format!("Could not write file \"{}\": {}.", path, e)))?;
+        Ok(Value::Nil)
+    }));
+
+    // The names of the entries in a directory, sorted.
+    interpreter.define_native(NativeFunction::new("listDir", 1, |interpreter, args| {
+        allow_fs(interpreter)?;
+        let path = string_argument("listDir", &args[0])?;
+        let list_error =
+            |e: io::Error| Unwind::error(&format!("Could not list directory \"{}\": {}.", path, e));
+        let mut names = Vec::new();
+        for entry in fs::read_dir(path).map_err(list_error)? {
+            let name = entry.map_err(list_error)?.file_name();
+            names.push(name.to_string_lossy().into_owned());
+        }
+        names.sort();
+        interpreter.allocate(
+            names
+                .iter()
+                .map(|name| name.len() + size_of::<Value>())
+                .sum(),
+        )?;
+        Ok(Value::from(
+            names.into_iter().map(Value::from).collect::<Vec<Value>>(),
+        ))
+    }));
+
+    // The value of an environment variable, or nil if it isn't set.
+    interpreter.define_native(NativeFunction::new("getenv", 1, |interpreter, args| {
+        allow_env(interpreter)?;
+        let name = string_argument("getenv", &args[0])?;
+        match env::var(name) {
+            Ok(value) => new_string(interpreter, value),
+            Err(_) => Ok(Value::Nil),
+        }
+    }));
+
+    // The arguments given after the script's path.
+    interpreter.define_native(NativeFunction::new("args", 0, |interpreter, _| {
+        allow_env(interpreter)?;
+        let args: Vec<Value> = interpreter
+            .args()
+            .iter()
+            .map(|arg| Value::from(arg.as_str()))
+            .collect();
+        interpreter.allocate(args.len() * size_of::<Value>())?;
+        Ok(Value::from(args))
+    }));
+}
+
+fn allow_fs(interpreter: &Interpreter) -> Result<(), Unwind> {
+    if !interpreter.permissions().fs {
+        return Err(Unwind::error(
+            "File access is disabled. Run with --allow-fs to enable it.",
+        ));
+    }
+    Ok(())
+}
+
+fn allow_env(interpreter: &Interpreter) -> Result<(), Unwind> {
+    if !interpreter.permissions().env {
+        return Err(Unwind::error(
+            "Environment access is disabled. Run with --allow-env to enable it.",
+        ));
+    }
+    Ok(())
+}
+
+// The `math` global: numeric functions and constants, used as `math.sqrt(2)`.
+fn math_module() -> LoxModule {
+    let functions = [
+        unary_math("floor", f64::floor),
+        unary_math("ceil", f64::ceil),
+        // Halfway cases round away from zero.
+        unary_math("round", f64::round),
+        unary_math("abs", f64::abs),
+        unary_math("sqrt", f64::sqrt),
+        unary_math("sin", f64::sin),
+        unary_math("cos", f64::cos),
+        unary_math("tan", f64::tan),
+        unary_math("asin", f64::asin),
+        unary_math("acos", f64::acos),
+        unary_math("atan", f64::atan),
+        binary_math("atan2", f64::atan2),
+        binary_math("pow", f64::powf),
+        binary_math("min", f64::min),
+        binary_math("max", f64::max),
+        // Integer division, rounding toward zero so that `div(a, b) * b + a % b == a`.
+        binary_math("div", |a, b| (a / b).trunc()),
+        // A number from 0 up to but not including 1.
+        NativeFunction::new("random", 0, |interpreter, _| {
+            Ok(Value::Number(interpreter.random().next_f64()))
+        }),
+        // Restarts the random numbers, so the same seed always gives the same sequence.
+        NativeFunction::new("seed", 1, |interpreter, args| match &args[0] {
+            Value::Number(seed) if seed.fract() == 0.0 => {
+                *interpreter.random() = Random::new(*seed as i64 as u64);
+                Ok(Value::Nil)
+            }
+            _ => Err(Unwind::error("Seed must be an integer.")),
+        }),
+    ];
+
+    let globals = Environment::new(None);
+    let mut exports = Vec::new();
+    for (name, value) in [("pi", consts::PI), ("e", consts::E)] {
+        globals.borrow_mut().define(name, Value::Number(value));
+        exports.push(name.to_string());
+    }
+    for function in functions {
+        exports.push(function.name.clone());
+        let name = function.name.clone();
+        globals
+            .borrow_mut()
+            .define(&name, Value::Native(Rc::new(function)));
+    }
+    LoxModule {
+        name: "math".to_string(),
+        globals,
+        exports,
+    }
+}
+
+fn unary_math(name: &'static str, function: fn(f64) -> f64) -> NativeFunction {
+    NativeFunction::new(name, 1, move |_, args| {
+        Ok(Value::Number(function(math_operand(name, &args[0])?)))
+    })
+}
+
+fn binary_math(name: &'static str, function: fn(f64, f64) -> f64) -> NativeFunction {
+    NativeFunction::new(name, 2, move |_, args| {
+        let a = math_operand(name, &args[0])?;
+        let b = math_operand(name, &args[1])?;
+        Ok(Value::Number(function(a, b)))
+    })
+}
+
+fn math_operand(name: &str, value: &Value) -> Result<f64, Unwind> {
+    match value {
+        Value::Number(n) => Ok(*n),
+        other => Err(Unwind::error(&format!(
+            "math.{}() expects numbers, not {}.",
+            name,
+            other.type_name()
+        ))),
+    }
+}
+
+/// The generator behind `math.random()`, SplitMix64. Fast and good enough for games and
+/// tests, but not for anything that needs to be unpredictable.
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random { state: seed }
+    }
+
+    /// Seeded from the clock, so each run gets different numbers.
+    pub fn from_clock() -> Random {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_nanos() as u64);
+        Random::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number from 0 up to but not including 1.
+    pub fn next_f64(&mut self) -> f64 {
+        // The top 53 bits fill a double's mantissa exactly.
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// The built-in method `name` of a list, bound to that list. None when lists have no such
+/// method.
+pub fn list_method(list: &Rc<RefCell<Vec<Value>>>, name: &str) -> Option<NativeFunction> {
+    let list = Rc::clone(list);
+    Some(match name {
+        "push" => NativeFunction::new(name, 1, move |interpreter, args| {
+            interpreter.allocate(size_of::<Value>())?;
+            list.borrow_mut().push(args[0].clone());
+            Ok(Value::Nil)
+        }),
+        "pop" => NativeFunction::new(name, 0, move |_, _| {
+            list.borrow_mut()
+                .pop()
+                .ok_or_else(|| Unwind::error("Can't pop from an empty list."))
+        }),
+        "len" => NativeFunction::new(name, 0, move |_, _| {
+            Ok(Value::Number(list.borrow().len() as f64))
+        }),
+        // Inserting at the length appends.
+        "insert" => NativeFunction::new(name, 2, move |interpreter, args| {
+            let index = list_index(&args[0], list.borrow().len() + 1)?;
+            interpreter.allocate(size_of::<Value>())?;
+            list.borrow_mut().insert(index, args[1].clone());
+            Ok(Value::Nil)
+        }),
+        "remove" => NativeFunction::new(name, 1, move |_, args| {
+            let index = list_index(&args[0], list.borrow().len())?;
+            Ok(list.borrow_mut().remove(index))
+        }),
+        // A new list with the elements from `start` up to but not including `end`.
+        "slice" => NativeFunction::new(name, 2, move |interpreter, args| {
+            let len = list.borrow().len();
+            let start = list_index(&args[0], len + 1)?;
+            let end = list_index(&args[1], len + 1)?;
+            if start > end {
+                return Err(Unwind::error("Slice start must not be after its end."));
+            }
+            interpreter.allocate((end - start) * size_of::<Value>())?;
+            Ok(Value::from(list.borrow()[start..end].to_vec()))
+        }),
+        _ => return None,
+    })
+}
+
+/// Checks that `index` is a whole number below `len` and converts it.
+pub fn list_index(index: &Value, len: usize) -> Result<usize, Unwind> {
+    checked_index("List", index, len)
+}
+
+// Like `list_index`, naming `what` is being indexed in its errors.
+fn checked_index(what: &str, index: &Value, len: usize) -> Result<usize, Unwind> {
+    let Value::Number(n) = index else {
+        return Err(Unwind::error(&format!(
+            "{} index must be a number, not {}.",
+            what,
+            index.type_name()
+        )));
+    };
+    if n.fract() != 0.0 {
+        return Err(Unwind::error(&format!(
+            "{} index must be an integer.",
+            what
+        )));
+    }
+    if *n < 0.0 || *n >= len as f64 {
+        return Err(Unwind::error(&format!(
+            "{} index {} is out of bounds for length {}.",
+            what, n, len
+        )));
+    }
+    Ok(*n as usize)
+}
+
+/// The built-in method `name` of a string, bound to that string. None when strings have no
+/// such method. Positions count chars, the same as the lexer does, not bytes.
+pub fn string_method(string: &Rc<str>, name: &str) -> Option<NativeFunction> {
+    let string = Rc::clone(string);
+    Some(match name {
+        "len" => NativeFunction::new(name, 0, move |_, _| {
+            Ok(Value::Number(string.chars().count() as f64))
+        }),
+        // The chars from `start` up to but not including `end`.
+        "substring" => NativeFunction::new(name, 2, move |interpreter, args| {
+            let len = string.chars().count();
+            let start = checked_index("String", &args[0], len + 1)?;
+            let end = checked_index("String", &args[1], len + 1)?;
+            if start > end {
+                return Err(Unwind::error("Substring start must not be after its end."));
+            }
+            let substring: String = string.chars().skip(start).take(end - start).collect();
+            new_string(interpreter, substring)
+        }),
+        "charAt" => NativeFunction::new(name, 1, move |interpreter, args| {
+            let index = checked_index("String", &args[0], string.chars().count())?;
+            new_string(interpreter, string.chars().nth(index).into_iter().collect())
+        }),
+        // The position of the first occurrence of `part`, or -1 if there is none.
+        "indexOf" => NativeFunction::new(name, 1, move |_, args| {
+            let part = string_argument("indexOf", &args[0])?;
+            Ok(Value::Number(match string.find(part) {
+                Some(byte) => string[..byte].chars().count() as f64,
+                None => -1.0,
+            }))
+        }),
+        // An empty separator splits the string into its chars.
+        "split" => NativeFunction::new(name, 1, move |interpreter, args| {
+            let separator = string_argument("split", &args[0])?;
+            let parts: Vec<Value> = if separator.is_empty() {
+                string.chars().map(|c| Value::from(c.to_string())).collect()
+            } else {
+                string.split(separator).map(Value::from).collect()
+            };
+            interpreter.allocate(parts.len() * size_of::<Value>() + string.len())?;
+            Ok(Value::from(parts))
+        }),
+        // The elements of a list, shown as `print` would, with this string between them.
+        "join" => NativeFunction::new(name, 1, move |interpreter, args| {
+            let Value::List(list) = &args[0] else {
+                return Err(Unwind::error(&format!(
+                    "join() expects a list, not {}.",
+                    args[0].type_name()
+                )));
+            };
+            let parts: Vec<String> = list.borrow().iter().map(Value::to_string).collect();
+            new_string(interpreter, parts.join(&string))
+        }),
+        // Replaces every occurrence of `from`.
+        "replace" => NativeFunction::new(name, 2, move |interpreter, args| {
+            let from = string_argument("replace", &args[0])?;
+            let to = string_argument("replace", &args[1])?;
+            new_string(interpreter, string.replace(from, to))
+        }),
+        "trim" => NativeFunction::new(name, 0, move |interpreter, _| {
+            new_string(interpreter, string.trim().to_string())
+        }),
+        "upper" => NativeFunction::new(name, 0, move |interpreter, _| {
+            new_string(interpreter, string.to_uppercase())
+        }),
+        "lower" => NativeFunction::new(name, 0, move |interpreter, _| {
+            new_string(interpreter, string.to_lowercase())
+        }),
+        "startsWith" => NativeFunction::new(name, 1, move |_, args| {
+            Ok(Value::Bool(
+                string.starts_with(string_argument("startsWith", &args[0])?),
+            ))
+        }),
+        "endsWith" => NativeFunction::new(name, 1, move |_, args| {
+            Ok(Value::Bool(
+                string.ends_with(string_argument("endsWith", &args[0])?),
+            ))
+        }),
+        "format" => NativeFunction::new(name, 1, move |interpreter, args| {
+            let formatted = format_string(&string, &args[0])?;
+            new_string(interpreter, formatted)
+        }),
+        _ => return None,
+    })
+}
+
+fn string_argument<'a>(method: &str, value: &'a Value) -> Result<&'a str, Unwind> {
+    match value {
+        Value::String(s) => Ok(s),
+        other => Err(Unwind::error(&format!(
+            "{}() expects a string, not {}.",
+            method,
+            other.type_name()
+        ))),
+    }
+}
+
+fn new_string(interpreter: &mut Interpreter, string: String) -> Result<Value, Unwind> {
+    interpreter.allocate(string.len())?;
+    Ok(Value::from(string))
+}
+
+// Fills in the placeholders of a `format` template. With a list, `{}` takes the next element
+// and `{1}` the element at that position. With a map, `{name}` takes the value of the key
+// "name". `{{` and `}}` stand for literal braces.
+fn format_string(template: &str, arguments: &Value) -> Result<String, Unwind> {
+    let mut out = String::new();
+    let mut next = 0;
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('{') if placeholder.is_empty() => {
+                            out.push('{');
+                            break;
+                        }
+                        Some('}') => {
+                            out.push_str(&format_argument(&placeholder, arguments, &mut next)?);
+                            break;
+                        }
+                        Some(c) => placeholder.push(c),
+                        None => return Err(Unwind::error("Unclosed '{' in format string.")),
+                    }
+                }
+            }
+            '}' => {
+                if chars.next() != Some('}') {
+                    return Err(Unwind::error("Unmatched '}' in format string."));
+                }
+                out.push('}');
+            }
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+fn format_argument(
+    placeholder: &str,
+    arguments: &Value,
+    next: &mut usize,
+) -> Result<String, Unwind> {
+    let value = match arguments {
+        Value::List(list) => {
+            let list = list.borrow();
+            let index = if placeholder.is_empty() {
+                *next += 1;
+                *next - 1
+            } else {
+                placeholder.parse::<usize>().map_err(|_| {
+                    Unwind::error(&format!(
+                        "Format placeholder {{{}}} must be empty or a position.",
+                        placeholder
+                    ))
+                })?
+            };
+            list.get(index).cloned().ok_or_else(|| {
+                Unwind::error(&format!(
+                    "Format placeholder {} is out of bounds for {} arguments.",
+                    index,
+                    list.len()
+                ))
+            })?
+        }
+        Value::Map(map) => {
+            let key = MapKey::new(&Value::from(placeholder)).expect("strings are map keys");
+            map.borrow().get(&key).cloned().ok_or_else(|| {
+                Unwind::error(&format!("Undefined format key \"{}\".", placeholder))
+            })?
+        }
+        other => {
+            return Err(Unwind::error(&format!(
+                "format() expects a list or a map, not {}.",
+                other.type_name()
+            )));
+        }
+    };
+    Ok(value.to_string())
+}
+
+/// The built-in method `name` of a map, bound to that map. None when maps have no such
+/// method.
+pub fn map_method(map: &Rc<RefCell<LoxMap>>, name: &str) -> Option<NativeFunction> {
+    let map = Rc::clone(map);
+    Some(match name {
+        "keys" => NativeFunction::new(name, 0, move |interpreter, _| {
+            let keys: Vec<Value> = map.borrow().iter().map(|(key, _)| key.to_value()).collect();
+            interpreter.allocate(keys.len() * size_of::<Value>())?;
+            Ok(Value::from(keys))
+        }),
+        "values" => NativeFunction::new(name, 0, move |interpreter, _| {
+            let values: Vec<Value> = map
+                .borrow()
+                .iter()
+                .map(|(_, value)| value.clone())
+                .collect();
+            interpreter.allocate(values.len() * size_of::<Value>())?;
+            Ok(Value::from(values))
+        }),
+        "has" => NativeFunction::new(name, 1, move |_, args| {
+            Ok(Value::Bool(map.borrow().contains(&map_key(&args[0])?)))
+        }),
+        // Returns whether the key was there.
+        "delete" => NativeFunction::new(name, 1, move |_, args| {
+            let key = map_key(&args[0])?;
+            Ok(Value::Bool(map.borrow_mut().remove(&key).is_some()))
+        }),
+        "len" => NativeFunction::new(name, 0, move |_, _| {
+            Ok(Value::Number(map.borrow().len() as f64))
+        }),
+        _ => return None,
+    })
+}
+
+/// Checks that `key` can be used as a map key and converts it.
+pub fn map_key(key: &Value) -> Result<MapKey, Unwind> {
+    MapKey::new(key).ok_or_else(|| {
+        Unwind::error(&format!(
+            "Map keys must be strings, numbers, booleans or nil, not {}.",
+            key.type_name()
+        ))
+    })
+}
@@ -0,0 +1,339 @@
+use std::rc::Rc;
+
+use crate::lexer::TokenType;
+use crate::parser::Expr;
+use crate::parser::FunctionDecl;
+use crate::parser::LiteralValue;
+use crate::parser::Stmt;
+use crate::value::Value;
+
+/// Rewrites a resolved program into a cheaper one that behaves the same. Operators on
+/// literals are evaluated ahead of time, groupings are dropped since the tree already
+/// encodes precedence, and `if` and `while` statements whose condition is a constant lose
+/// the branches that can never run.
+///
+/// Expressions that would fail at runtime, like `-"a"` or `1 + nil`, are left for the
+/// interpreter to report. Variable expressions keep their ids, so the resolver's results
+/// still apply to the optimized tree. Fewer expressions get evaluated, which counts against
+/// `Limits::max_steps`, and folded string concatenations are not charged to
+/// `Limits::max_allocated_bytes`.
+pub fn optimize(statements: &[Stmt]) -> Vec<Stmt> {
+    let mut optimized = block(statements);
+    // `Interpreter::eval` returns the value of a trailing expression statement, so one that
+    // was followed by a removed statement must not end up last.
+    if statements
+        .last()
+        .is_some_and(|last| !matches!(last, Stmt::Expression { .. }))
+        && optimized
+            .last()
+            .is_some_and(|last| matches!(last, Stmt::Expression { .. }))
+    {
+        optimized.push(Stmt::Block {
+            statements: Vec::new(),
+        });
+    }
+    optimized
+}
+
+fn block(statements: &[Stmt]) -> Vec<Stmt> {
+    statements.iter().filter_map(statement).collect()
+}
+
+// The optimized statement, or None when it can never do anything.
+fn statement(stmt: &Stmt) -> Option<Stmt> {
+    Some(match stmt {
+        Stmt::Expression { expr } => Stmt::Expression {
+            expr: expression(expr),
+        },
+        Stmt::Print { expr } => Stmt::Print {
+            expr: expression(expr),
+        },
+        Stmt::Var { name, initializer } => Stmt::Var {
+            name: name.clone(),
+            initializer: initializer.as_ref().map(expression),
+        },
+        Stmt::Block { statements } => Stmt::Block {
+            statements: block(statements),
+        },
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            let condition = expression(condition);
+            let Some(truthy) = constant_truthiness(&condition) else {
+                return Some(Stmt::If {
+                    condition,
+                    then_branch: Box::new(branch(then_branch)),
+                    else_branch: else_branch.as_deref().map(|b| Box::new(branch(b))),
+                });
+            };
+            if truthy {
+                statement(then_branch)?
+            } else {
+                statement(else_branch.as_deref()?)?
+            }
+        }
+        Stmt::While {
+            condition,
+            body,
+            increment,
+        } => {
+            let condition = expression(condition);
+            if constant_truthiness(&condition) == Some(false) {
+                return None;
+            }
+            Stmt::While {
+                condition,
+                body: Box::new(branch(body)),
+                increment: increment.as_ref().map(expression),
+            }
+        }
+        Stmt::ForIn {
+            name,
+            iterable,
+            body,
+        } => Stmt::ForIn {
+            name: name.clone(),
+            iterable: expression(iterable),
+            body: Box::new(branch(body)),
+        },
+        Stmt::Function { decl } => Stmt::Function {
+            decl: function(decl),
+        },
+        Stmt::Return { keyword, value } => Stmt::Return {
+            keyword: keyword.clone(),
+            value: value.as_ref().map(expression),
+        },
+        Stmt::Break { .. } | Stmt::Continue { .. } | Stmt::Import { .. } => stmt.clone(),
+        Stmt::Throw { keyword, value } => Stmt::Throw {
+            keyword: keyword.clone(),
+            value: expression(value),
+        },
+        Stmt::Try {
+            body,
+            catch,
+            finally,
+        } => Stmt::Try {
+            body: block(body),
+            catch: catch
+                .as_ref()
+                .map(|(name, handler)| (name.clone(), block(handler))),
+            finally: finally.as_deref().map(block),
+        },
+        Stmt::Class {
+            name,
+            superclass,
+            methods,
+        } => Stmt::Class {
+            name: name.clone(),
+            superclass: superclass.as_ref().map(expression),
+            methods: methods.iter().map(|method| function(method)).collect(),
+        },
+    })
+}
+
+// A statement nested in another one can't simply disappear, so it becomes an empty block.
+fn branch(stmt: &Stmt) -> Stmt {
+    statement(stmt).unwrap_or(Stmt::Block {
+        statements: Vec::new(),
+    })
+}
+
+fn function(decl: &FunctionDecl) -> Rc<FunctionDecl> {
+    Rc::new(FunctionDecl {
+        name: decl.name.clone(),
+        params: decl.params.clone(),
+        body: block(&decl.body),
+    })
+}
+
+fn expression(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Grouping { expr: inner } => expression(inner),
+        Expr::Stringify { expr: inner } => match expression(inner) {
+            Expr::Literal { value, line } => Expr::Literal {
+                value: LiteralValue::String(display(&value)),
+                line,
+            },
+            inner => Expr::Stringify {
+                expr: Box::new(inner),
+            },
+        },
+        Expr::Unary { op, right } => {
+            let right = expression(right);
+            let folded = match (op.token_type, &right) {
+                (
+                    TokenType::Minus,
+                    Expr::Literal {
+                        value: LiteralValue::Number(n),
+                        ..
+                    },
+                ) => Some(LiteralValue::Number(-n)),
+                (TokenType::Bang, Expr::Literal { value, .. }) => {
+                    Some(LiteralValue::Bool(!is_truthy(value)))
+                }
+                _ => None,
+            };
+            match folded {
+                Some(value) => Expr::Literal {
+                    value,
+                    line: op.line,
+                },
+                None => Expr::Unary {
+                    op: op.clone(),
+                    right: Box::new(right),
+                },
+            }
+        }
+        Expr::Binary { left, op, right } => {
+            let left = expression(left);
+            let right = expression(right);
+            if let (Expr::Literal { value: a, .. }, Expr::Literal { value: b, .. }) =
+                (&left, &right)
+                && let Some(value) = binary(op.token_type, a, b)
+            {
+                return Expr::Literal {
+                    value,
+                    line: op.line,
+                };
+            }
+            Expr::Binary {
+                left: Box::new(left),
+                op: op.clone(),
+                right: Box::new(right),
+            }
+        }
+        Expr::Logical { left, op, right } => {
+            let left = expression(left);
+            let right = expression(right);
+            if let Some(truthy) = constant_truthiness(&left) {
+                let short_circuits = if op.token_type == TokenType::Or {
+                    truthy
+                } else {
+                    !truthy
+                };
+                return if short_circuits { left } else { right };
+            }
+            Expr::Logical {
+                left: Box::new(left),
+                op: op.clone(),
+                right: Box::new(right),
+            }
+        }
+        Expr::Assign { id, name, value } => Expr::Assign {
+            id: *id,
+            name: name.clone(),
+            value: Box::new(expression(value)),
+        },
+        Expr::Call {
+            callee,
+            paren,
+            arguments,
+        } => Expr::Call {
+            callee: Box::new(expression(callee)),
+            paren: paren.clone(),
+            arguments: arguments.iter().map(expression).collect(),
+        },
+        Expr::Get { object, name } => Expr::Get {
+            object: Box::new(expression(object)),
+            name: name.clone(),
+        },
+        Expr::Set {
+            object,
+            name,
+            value,
+        } => Expr::Set {
+            object: Box::new(expression(object)),
+            name: name.clone(),
+            value: Box::new(expression(value)),
+        },
+        Expr::List { bracket, elements } => Expr::List {
+            bracket: bracket.clone(),
+            elements: elements.iter().map(expression).collect(),
+        },
+        Expr::Map { brace, entries } => Expr::Map {
+            brace: brace.clone(),
+            entries: entries
+                .iter()
+                .map(|(key, value)| (expression(key), expression(value)))
+                .collect(),
+        },
+        Expr::Index {
+            object,
+            bracket,
+            index,
+        } => Expr::Index {
+            object: Box::new(expression(object)),
+            bracket: bracket.clone(),
+            index: Box::new(expression(index)),
+        },
+        Expr::SetIndex {
+            object,
+            bracket,
+            index,
+            value,
+        } => Expr::SetIndex {
+            object: Box::new(expression(object)),
+            bracket: bracket.clone(),
+            index: Box::new(expression(index)),
+            value: Box::new(expression(value)),
+        },
+        Expr::Literal { .. } | Expr::Variable { .. } | Expr::This { .. } | Expr::Super { .. } => {
+            expr.clone()
+        }
+    }
+}
+
+// Applies a binary operator to two literals the way the interpreter would, or None when the
+// interpreter would report an error instead.
+fn binary(op: TokenType, a: &LiteralValue, b: &LiteralValue) -> Option<LiteralValue> {
+    match op {
+        TokenType::EqualEqual => return Some(LiteralValue::Bool(a == b)),
+        TokenType::BangEqual => return Some(LiteralValue::Bool(a != b)),
+        TokenType::Plus => {
+            if let (LiteralValue::String(a), LiteralValue::String(b)) = (a, b) {
+                return Some(LiteralValue::String(format!("{}{}", a, b)));
+            }
+        }
+        _ => {}
+    }
+
+    let (LiteralValue::Number(a), LiteralValue::Number(b)) = (a, b) else {
+        return None;
+    };
+    Some(match op {
+        TokenType::Plus => LiteralValue::Number(a + b),
+        TokenType::Minus => LiteralValue::Number(a - b),
+        TokenType::Star => LiteralValue::Number(a * b),
+        TokenType::Slash => LiteralValue::Number(a / b),
+        TokenType::Percent => LiteralValue::Number(a % b),
+        TokenType::Greater => LiteralValue::Bool(a > b),
+        TokenType::GreaterEqual => LiteralValue::Bool(a >= b),
+        TokenType::Less => LiteralValue::Bool(a < b),
+        TokenType::LessEqual => LiteralValue::Bool(a <= b),
+        _ => return None,
+    })
+}
+
+// The text the interpreter would show for the value.
+fn display(value: &LiteralValue) -> String {
+    match value {
+        LiteralValue::Nil => Value::Nil,
+        LiteralValue::Bool(b) => Value::Bool(*b),
+        LiteralValue::Number(n) => Value::Number(*n),
+        LiteralValue::String(s) => return s.clone(),
+    }
+    .to_string()
+}
+
+fn is_truthy(value: &LiteralValue) -> bool {
+    !matches!(value, LiteralValue::Nil | LiteralValue::Bool(false))
+}
+
+fn constant_truthiness(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Literal { value, .. } => Some(is_truthy(value)),
+        _ => None,
+    }
+}
@@ -1,190 +1,1068 @@
+use std::fmt::Formatter;
 use std::iter::Peekable;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::lexer::Lexer;
 use crate::lexer::Token;
 use crate::lexer::TokenType;
 
-enum Expr {
+// Every expression that refers to a variable gets a unique id so the resolver can record how
+// many scopes away its binding lives. The counter is global so that ids stay unique across
+// separate parses feeding the same interpreter.
+static NEXT_EXPR_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn next_expr_id() -> usize {
+    NEXT_EXPR_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+const MAX_ARGUMENTS: usize = 255;
+
+// Deeper nesting than this is reported as an error rather than risking the Rust stack, which
+// the parser, resolver and interpreter all recurse on. It assumes the generous stack of
+// `INTERPRETER_STACK_SIZE`, which the command line tools run with.
+const MAX_NESTING: usize = 200;
+
+// The branches of an `else if` chain sit side by side rather than nested, but each one still
+// recurses, so a chain can only be this long.
+const MAX_ELSE_BRANCHES: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiteralValue {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
     Binary {
         left: Box<Expr>,
-        op: TokenType,
+        op: Token,
         right: Box<Expr>,
     },
     Unary {
-        op: TokenType,
+        op: Token,
         right: Box<Expr>,
     },
     Literal {
-        value: String,
+        value: LiteralValue,
+        line: usize,
     },
     Grouping {
-        expr: Box<Expr>
+        expr: Box<Expr>,
+    },
+    /// The display form of a value, as a string. Interpolated strings are desugared into
+    /// concatenations of their text and these.
+    Stringify {
+        expr: Box<Expr>,
+    },
+    Logical {
+        left: Box<Expr>,
+        op: Token,
+        right: Box<Expr>,
+    },
+    Variable {
+        id: usize,
+        name: Token,
+    },
+    Assign {
+        id: usize,
+        name: Token,
+        value: Box<Expr>,
+    },
+    Call {
+        callee: Box<Expr>,
+        paren: Token,
+        arguments: Vec<Expr>,
+    },
+    Get {
+        object: Box<Expr>,
+        name: Token,
+    },
+    Set {
+        object: Box<Expr>,
+        name: Token,
+        value: Box<Expr>,
+    },
+    List {
+        bracket: Token,
+        elements: Vec<Expr>,
+    },
+    // A map literal, with its entries in source order.
+    Map {
+        brace: Token,
+        entries: Vec<(Expr, Expr)>,
+    },
+    Index {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    },
+    SetIndex {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
+    This {
+        id: usize,
+        keyword: Token,
+    },
+    Super {
+        id: usize,
+        keyword: Token,
+        method: Token,
+    },
+}
+
+impl Expr {
+    /// The source line of the expression, if any token in it records one.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Expr::Binary { op, .. } | Expr::Unary { op, .. } | Expr::Logical { op, .. } => {
+                Some(op.line)
+            }
+            Expr::Literal { line, .. } => Some(*line),
+            Expr::Grouping { expr } | Expr::Stringify { expr } => expr.line(),
+            Expr::Variable { name, .. }
+            | Expr::Assign { name, .. }
+            | Expr::Get { name, .. }
+            | Expr::Set { name, .. } => Some(name.line),
+            Expr::Call { paren, .. } => Some(paren.line),
+            Expr::List { bracket, .. }
+            | Expr::Index { bracket, .. }
+            | Expr::SetIndex { bracket, .. } => Some(bracket.line),
+            Expr::Map { brace, .. } => Some(brace.line),
+            Expr::This { keyword, .. } | Expr::Super { keyword, .. } => Some(keyword.line),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Expression {
+        expr: Expr,
+    },
+    Print {
+        expr: Expr,
+    },
+    Var {
+        name: Token,
+        initializer: Option<Expr>,
+    },
+    Block {
+        statements: Vec<Stmt>,
+    },
+    If {
+        condition: Expr,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+    },
+    // `increment` is the third clause of a `for` loop, which runs after the body even when
+    // it is cut short by `continue`.
+    While {
+        condition: Expr,
+        body: Box<Stmt>,
+        increment: Option<Expr>,
+    },
+    // `for (var name in iterable) body`, with `name` bound afresh for each item.
+    ForIn {
+        name: Token,
+        iterable: Expr,
+        body: Box<Stmt>,
+    },
+    Function {
+        decl: Rc<FunctionDecl>,
+    },
+    Return {
+        keyword: Token,
+        value: Option<Expr>,
+    },
+    Break {
+        keyword: Token,
+    },
+    Throw {
+        keyword: Token,
+        value: Expr,
+    },
+    // At least one of `catch` and `finally` is present.
+    Try {
+        body: Vec<Stmt>,
+        catch: Option<(Token, Vec<Stmt>)>,
+        finally: Option<Vec<Stmt>>,
+    },
+    Continue {
+        keyword: Token,
+    },
+    // `import "path" as alias;` binds the module itself, `from "path" import a, b;` binds
+    // some of its exports. `keyword` is the first token, and exactly one of `alias` and
+    // `names` is used.
+    Import {
+        keyword: Token,
+        path: Token,
+        alias: Option<Token>,
+        names: Vec<Token>,
+    },
+    Class {
+        name: Token,
+        superclass: Option<Expr>,
+        methods: Vec<Rc<FunctionDecl>>,
+    },
+}
+
+/// An error found before the program runs, by either the parser or the resolver.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub line: usize,
+    /// The column of the offending token, counting chars from 1.
+    pub column: usize,
+    /// Either empty, " at end" or " at '<lexeme>'".
+    pub location: String,
+    pub message: String,
+}
+
+impl CompileError {
+    pub fn at_token(token: &Token, message: &str) -> CompileError {
+        CompileError {
+            line: token.line,
+            column: token.column,
+            location: format!(" at '{}'", token.lexeme),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "[line {}] Error{}: {}",
+            self.line, self.location, self.message
+        )
     }
 }
 
-struct Parser<'a> {
+pub struct Parser<'a> {
     lexer: Peekable<&'a mut Lexer>,
+    previous: Option<Token>,
+    errors: Vec<CompileError>,
+    depth: usize,
+    // `else` branches the parser is inside of, which don't count towards `depth`.
+    else_depth: usize,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(lexer: &'a mut Lexer) -> Self {
-        // let first_token = lexer.next().expect("Lexer does not contain any tokens!");
         Self {
             lexer: lexer.peekable(),
+            previous: None,
+            errors: Vec::new(),
+            depth: 0,
+            else_depth: 0,
         }
     }
 
-    fn expr(&mut self) -> Result<Expr, ()> {
-        self.equality()
+    /// Parses the whole token stream. On failure every error found is returned, the parser
+    /// resynchronizes at statement boundaries after each one.
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<CompileError>> {
+        let mut statements: Vec<Stmt> = Vec::new();
+        while self.peek().is_some() {
+            match self.declaration() {
+                Ok(stmt) => statements.push(stmt),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize();
+                }
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
     }
 
-    fn equality(&mut self) -> Result<Expr, ()> {
-        let expr: Expr = self.comparison()?;
+    fn declaration(&mut self) -> Result<Stmt, CompileError> {
+        if self.matches(&[TokenType::Class]).is_some() {
+            return self.class_declaration();
+        }
+        if self.matches(&[TokenType::Fun]).is_some() {
+            return Ok(Stmt::Function {
+                decl: self.function("function")?,
+            });
+        }
+        if self.matches(&[TokenType::Var]).is_some() {
+            return self.var_declaration();
+        }
+        if let Some(keyword) = self.matches(&[TokenType::Import, TokenType::From]) {
+            return self.import_declaration(keyword);
+        }
+        self.statement()
+    }
 
-        let peeked_token: Option<&Token> = self.lexer.peek();
-        match peeked_token {
-            Some(t) => {
-                if t.token_type == TokenType::BangEqual || t.token_type == TokenType::EqualEqual {
-                    let expr_right = self.comparison()?;
-                    return Ok(Expr::Binary {
-                        left: Box::new(expr),
-                        op: self.lexer.next().unwrap().token_type,
-                        right: Box::new(expr_right),
-                    });
-                } else {
-                    return Ok(expr);
-                }
+    fn import_declaration(&mut self, keyword: Token) -> Result<Stmt, CompileError> {
+        let path = self.consume(TokenType::String, "Expect module path string.")?;
+        let mut alias = None;
+        let mut names = Vec::new();
+        if keyword.token_type == TokenType::Import {
+            // `as` is not a reserved word, so it is matched by its text.
+            if self.peek().is_none_or(|t| t.lexeme != "as") {
+                return Err(self.error_at_peek("Expect 'as' after module path."));
             }
-            None => {
-                todo!()
+            self.advance();
+            alias = Some(self.consume(TokenType::Identifier, "Expect module name.")?);
+        } else {
+            self.consume(TokenType::Import, "Expect 'import' after module path.")?;
+            loop {
+                names.push(self.consume(TokenType::Identifier, "Expect name to import.")?);
+                if self.matches(&[TokenType::Comma]).is_none() {
+                    break;
+                }
             }
         }
+        self.consume(TokenType::Semicolon, "Expect ';' after import.")?;
+        Ok(Stmt::Import {
+            keyword,
+            path,
+            alias,
+            names,
+        })
     }
 
-    fn comparison(&mut self) -> Result<Expr, ()> {
-        let expr: Expr = self.term()?;
-        let peeked_token = self.lexer.peek();
-        match peeked_token {
-            Some(t) => {
-                if t.token_type == TokenType::Greater
-                    || t.token_type == TokenType::GreaterEqual
-                    || t.token_type == TokenType::Less
-                    || t.token_type == TokenType::LessEqual
-                {
-                    let expr_right = self.term()?;
-                    return Ok(Expr::Binary {
-                        left: Box::new(expr),
-                        op: self.lexer.next().unwrap().token_type,
-                        right: Box::new(expr_right),
-                    });
-                } else {
-                    return Ok(expr);
-                }
-            }
-            None => {
-                todo!()
-            }
+    fn class_declaration(&mut self) -> Result<Stmt, CompileError> {
+        let name = self.consume(TokenType::Identifier, "Expect class name.")?;
+
+        let mut superclass = None;
+        if self.matches(&[TokenType::Less]).is_some() {
+            let superclass_name = self.consume(TokenType::Identifier, "Expect superclass name.")?;
+            superclass = Some(Expr::Variable {
+                id: next_expr_id(),
+                name: superclass_name,
+            });
         }
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && self.peek().is_some() {
+            methods.push(self.function("method")?);
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+
+        Ok(Stmt::Class {
+            name,
+            superclass,
+            methods,
+        })
     }
 
-    fn term(&mut self) -> Result<Expr, ()> {
-        let expr: Expr = self.factor()?;
-        let peeked_token = self.lexer.peek();
-        match peeked_token {
-            Some(t) => {
-                if t.token_type == TokenType::Minus || t.token_type == TokenType::Plus {
-                    let expr_right = self.factor()?;
-                    return Ok(Expr::Binary {
-                        left: Box::new(expr),
-                        op: self.lexer.next().unwrap().token_type,
-                        right: Box::new(expr_right),
-                    });
-                } else {
-                    return Ok(expr);
+    fn function(&mut self, kind: &str) -> Result<Rc<FunctionDecl>, CompileError> {
+        let name = self.consume(TokenType::Identifier, &format!("Expect {} name.", kind))?;
+        self.consume(
+            TokenType::LeftParen,
+            &format!("Expect '(' after {} name.", kind),
+        )?;
+
+        let mut params = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    let error = self.error_at_peek("Can't have more than 255 parameters.");
+                    self.errors.push(error);
+                }
+                params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
+                if self.matches(&[TokenType::Comma]).is_none() {
+                    break;
                 }
-            }
-            None => {
-                todo!()
             }
         }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("Expect '{{' before {} body.", kind),
+        )?;
+        let body = self.block()?;
+
+        Ok(Rc::new(FunctionDecl { name, params, body }))
     }
 
-    fn factor(&mut self) -> Result<Expr, ()> {
-        let expr: Expr = self.unary()?;
-        let peeked_token = self.lexer.peek();
-        match peeked_token {
-            Some(t) => {
-                if t.token_type == TokenType::Slash || t.token_type == TokenType::Star {
-                    let expr_right = self.unary()?;
-                    return Ok(Expr::Binary {
-                        left: Box::new(expr),
-                        op: self.lexer.next().unwrap().token_type,
-                        right: Box::new(expr_right),
-                    });
-                } else {
-                    return Ok(expr);
-                }
+    fn var_declaration(&mut self) -> Result<Stmt, CompileError> {
+        let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
+        self.var_initializer(name)
+    }
+
+    // The rest of a variable declaration after its name.
+    fn var_initializer(&mut self, name: Token) -> Result<Stmt, CompileError> {
+        let mut initializer = None;
+        if self.matches(&[TokenType::Equal]).is_some() {
+            initializer = Some(self.expr()?);
+        }
+
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+        Ok(Stmt::Var { name, initializer })
+    }
+
+    fn statement(&mut self) -> Result<Stmt, CompileError> {
+        if self.matches(&[TokenType::For]).is_some() {
+            return self.for_statement();
+        }
+        if self.matches(&[TokenType::If]).is_some() {
+            return self.if_statement();
+        }
+        if self.matches(&[TokenType::Print]).is_some() {
+            return self.print_statement();
+        }
+        if let Some(keyword) = self.matches(&[TokenType::Return]) {
+            return self.return_statement(keyword);
+        }
+        if self.matches(&[TokenType::While]).is_some() {
+            return self.while_statement();
+        }
+        if let Some(keyword) = self.matches(&[TokenType::Throw]) {
+            let value = self.expr()?;
+            self.consume(TokenType::Semicolon, "Expect ';' after thrown value.")?;
+            return Ok(Stmt::Throw { keyword, value });
+        }
+        if self.matches(&[TokenType::Try]).is_some() {
+            return self.try_statement();
+        }
+        if let Some(keyword) = self.matches(&[TokenType::Break]) {
+            self.consume(TokenType::Semicolon, "Expect ';' after 'break'.")?;
+            return Ok(Stmt::Break { keyword });
+        }
+        if let Some(keyword) = self.matches(&[TokenType::Continue]) {
+            self.consume(TokenType::Semicolon, "Expect ';' after 'continue'.")?;
+            return Ok(Stmt::Continue { keyword });
+        }
+        if self.matches(&[TokenType::LeftBrace]).is_some() {
+            return Ok(Stmt::Block {
+                statements: self.block()?,
+            });
+        }
+        self.expression_statement()
+    }
+
+    // A for loop has no node of its own, it is desugared into a while loop wrapped in blocks.
+    fn for_statement(&mut self) -> Result<Stmt, CompileError> {
+        let line = self.previous.as_ref().map_or(0, |t| t.line);
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.matches(&[TokenType::Semicolon]).is_some() {
+            None
+        } else if self.matches(&[TokenType::Var]).is_some() {
+            let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
+            if self.matches(&[TokenType::In]).is_some() {
+                return self.for_in_statement(name);
             }
-            None => {
-                todo!()
+            Some(self.var_initializer(name)?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let mut condition = None;
+        if !self.check(TokenType::Semicolon) {
+            condition = Some(self.expr()?);
+        }
+        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+
+        let mut increment = None;
+        if !self.check(TokenType::RightParen) {
+            increment = Some(self.expr()?);
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+
+        let mut body = Stmt::While {
+            condition: condition.unwrap_or(Expr::Literal {
+                value: LiteralValue::Bool(true),
+                line,
+            }),
+            body: Box::new(self.nested(Self::statement)?),
+            increment,
+        };
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block {
+                statements: vec![initializer, body],
+            };
+        }
+
+        Ok(body)
+    }
+
+    fn for_in_statement(&mut self, name: Token) -> Result<Stmt, CompileError> {
+        let iterable = self.expr()?;
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+        let body = Box::new(self.nested(Self::statement)?);
+        Ok(Stmt::ForIn {
+            name,
+            iterable,
+            body,
+        })
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt, CompileError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expr()?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
+
+        let then_branch = Box::new(self.nested(Self::statement)?);
+        let mut else_branch = None;
+        if self.matches(&[TokenType::Else]).is_some() {
+            if self.else_depth >= MAX_ELSE_BRANCHES {
+                return Err(self.error_at_peek("Too many 'else' branches."));
             }
+            self.else_depth += 1;
+            let statement = self.statement();
+            self.else_depth -= 1;
+            else_branch = Some(Box::new(statement?));
+        }
+
+        Ok(Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt, CompileError> {
+        let expr = self.expr()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+        Ok(Stmt::Print { expr })
+    }
+
+    fn return_statement(&mut self, keyword: Token) -> Result<Stmt, CompileError> {
+        let mut value = None;
+        if !self.check(TokenType::Semicolon) {
+            value = Some(self.expr()?);
+        }
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        Ok(Stmt::Return { keyword, value })
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt, CompileError> {
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'try'.")?;
+        let body = self.block()?;
+
+        let mut catch = None;
+        if self.matches(&[TokenType::Catch]).is_some() {
+            self.consume(TokenType::LeftParen, "Expect '(' after 'catch'.")?;
+            let name = self.consume(TokenType::Identifier, "Expect error variable name.")?;
+            self.consume(TokenType::RightParen, "Expect ')' after error variable.")?;
+            self.consume(TokenType::LeftBrace, "Expect '{' before catch body.")?;
+            catch = Some((name, self.block()?));
+        }
+        let mut finally = None;
+        if self.matches(&[TokenType::Finally]).is_some() {
+            self.consume(TokenType::LeftBrace, "Expect '{' after 'finally'.")?;
+            finally = Some(self.block()?);
+        }
+
+        if catch.is_none() && finally.is_none() {
+            return Err(self.error_at_peek("Expect 'catch' or 'finally' after try block."));
+        }
+        Ok(Stmt::Try {
+            body,
+            catch,
+            finally,
+        })
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, CompileError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expr()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+        let body = Box::new(self.nested(Self::statement)?);
+        Ok(Stmt::While {
+            condition,
+            body,
+            increment: None,
+        })
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, CompileError> {
+        let mut statements = Vec::new();
+        while !self.check(TokenType::RightBrace) && self.peek().is_some() {
+            statements.push(self.nested(Self::declaration)?);
         }
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
     }
 
-    fn unary(&mut self) -> Result<Expr, ()> {
-        let current_token = self.lexer.next();
-        match current_token {
-            Some(t) => {
-                if t.token_type == TokenType::Bang || t.token_type == TokenType::Minus {
-                    let expr_right = self.unary()?;
-                    return Ok(Expr::Unary {
-                        op: t.token_type,
-                        right: Box::new(expr_right),
+    fn expression_statement(&mut self) -> Result<Stmt, CompileError> {
+        let expr = self.expr()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        Ok(Stmt::Expression { expr })
+    }
+
+    fn expr(&mut self) -> Result<Expr, CompileError> {
+        self.nested(Self::assignment)
+    }
+
+    fn assignment(&mut self) -> Result<Expr, CompileError> {
+        let expr = self.or()?;
+
+        if let Some(equals) = self.matches(&[TokenType::Equal]) {
+            let value = self.expr()?;
+            match expr {
+                Expr::Variable { name, .. } => {
+                    return Ok(Expr::Assign {
+                        id: next_expr_id(),
+                        name,
+                        value: Box::new(value),
                     });
-                } else {
-                    return self.primary();
                 }
-            }
-            None => {
-                todo!()
+                Expr::Get { object, name } => {
+                    return Ok(Expr::Set {
+                        object,
+                        name,
+                        value: Box::new(value),
+                    });
+                }
+                Expr::Index {
+                    object,
+                    bracket,
+                    index,
+                } => {
+                    return Ok(Expr::SetIndex {
+                        object,
+                        bracket,
+                        index,
+                        value: Box::new(value),
+                    });
+                }
+                _ => {
+                    // Report without unwinding, the parser is not confused about where it is.
+                    self.errors.push(CompileError::at_token(
+                        &equals,
+                        "Invalid assignment target.",
+                    ));
+                }
             }
         }
+
+        Ok(expr)
     }
 
-    fn primary(&mut self) -> Result<Expr, ()> {
-        let current_token = self.lexer.next();
-        match current_token {
-            Some(t) => {
-                if t.token_type == TokenType::LeftParen {
-                    let expr = self.expr()?;
-                    // Consume the closing paranthesis
-                    self.lexer.next();
-                    return Ok(Expr::Grouping { expr: Box::new(expr) });
-                }
-                else {
-                    return Ok(Expr::Literal { value: t.lexeme });
-                }
-            },
-            None => {todo!()}
+    fn or(&mut self) -> Result<Expr, CompileError> {
+        let mut expr = self.and()?;
+        while let Some(op) = self.matches(&[TokenType::Or]) {
+            let right = self.and()?;
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                op,
+                right: Box::new(right),
+            };
         }
+        Ok(expr)
     }
-}
 
+    fn and(&mut self) -> Result<Expr, CompileError> {
+        let mut expr = self.equality()?;
+        while let Some(op) = self.matches(&[TokenType::And]) {
+            let right = self.equality()?;
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                op,
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
 
+    fn equality(&mut self) -> Result<Expr, CompileError> {
+        let mut expr: Expr = self.comparison()?;
+        while let Some(op) = self.matches(&[TokenType::BangEqual, TokenType::EqualEqual]) {
+            let expr_right = self.comparison()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                op,
+                right: Box::new(expr_right),
+            };
+        }
+        Ok(expr)
+    }
 
+    fn comparison(&mut self) -> Result<Expr, CompileError> {
+        let mut expr: Expr = self.term()?;
+        while let Some(op) = self.matches(&[
+            TokenType::Greater,
+            TokenType::GreaterEqual,
+            TokenType::Less,
+            TokenType::LessEqual,
+        ]) {
+            let expr_right = self.term()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                op,
+                right: Box::new(expr_right),
+            };
+        }
+        Ok(expr)
+    }
 
+    fn term(&mut self) -> Result<Expr, CompileError> {
+        let mut expr: Expr = self.factor()?;
+        while let Some(op) = self.matches(&[TokenType::Minus, TokenType::Plus]) {
+            let expr_right = self.factor()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                op,
+                right: Box::new(expr_right),
+            };
+        }
+        Ok(expr)
+    }
 
+    fn factor(&mut self) -> Result<Expr, CompileError> {
+        let mut expr: Expr = self.unary()?;
+        while let Some(op) = self.matches(&[TokenType::Slash, TokenType::Star, TokenType::Percent])
+        {
+            let expr_right = self.unary()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                op,
+                right: Box::new(expr_right),
+            };
+        }
+        Ok(expr)
+    }
 
+    fn unary(&mut self) -> Result<Expr, CompileError> {
+        if let Some(op) = self.matches(&[TokenType::Bang, TokenType::Minus]) {
+            let expr_right = self.nested(Self::unary)?;
+            return Ok(Expr::Unary {
+                op,
+                right: Box::new(expr_right),
+            });
+        }
+        self.call()
+    }
 
+    fn call(&mut self) -> Result<Expr, CompileError> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.matches(&[TokenType::LeftParen]).is_some() {
+                expr = self.finish_call(expr)?;
+            } else if self.matches(&[TokenType::Dot]).is_some() {
+                let name =
+                    self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+                expr = Expr::Get {
+                    object: Box::new(expr),
+                    name,
+                };
+            } else if let Some(bracket) = self.matches(&[TokenType::LeftBracket]) {
+                let index = self.expr()?;
+                self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+                expr = Expr::Index {
+                    object: Box::new(expr),
+                    bracket,
+                    index: Box::new(index),
+                };
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
 
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, CompileError> {
+        let mut arguments = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    let error = self.error_at_peek("Can't have more than 255 arguments.");
+                    self.errors.push(error);
+                }
+                arguments.push(self.expr()?);
+                if self.matches(&[TokenType::Comma]).is_none() {
+                    break;
+                }
+            }
+        }
+        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
 
+        Ok(Expr::Call {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        })
+    }
 
+    fn primary(&mut self) -> Result<Expr, CompileError> {
+        let (token_type, line) = match self.peek() {
+            Some(t) => (t.token_type, t.line),
+            None => return Err(self.error_at_peek("Expect expression.")),
+        };
 
+        let expr = match token_type {
+            TokenType::False => Expr::Literal {
+                value: LiteralValue::Bool(false),
+                line,
+            },
+            TokenType::True => Expr::Literal {
+                value: LiteralValue::Bool(true),
+                line,
+            },
+            TokenType::Nil => Expr::Literal {
+                value: LiteralValue::Nil,
+                line,
+            },
+            TokenType::Number => {
+                let t = self.advance().unwrap();
+                return Ok(Expr::Literal {
+                    value: LiteralValue::Number(t.lexeme.parse::<f64>().unwrap()),
+                    line,
+                });
+            }
+            TokenType::String => {
+                let t = self.advance().unwrap();
+                return Ok(Expr::Literal {
+                    value: LiteralValue::String(t.literal.unwrap_or_default()),
+                    line,
+                });
+            }
+            TokenType::Interpolation => return self.interpolation(),
+            TokenType::LeftBracket => {
+                let bracket = self.advance().unwrap();
+                let mut elements = Vec::new();
+                if !self.check(TokenType::RightBracket) {
+                    loop {
+                        elements.push(self.expr()?);
+                        if self.matches(&[TokenType::Comma]).is_none() {
+                            break;
+                        }
+                    }
+                }
+                self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+                return Ok(Expr::List { bracket, elements });
+            }
+            // Statements starting with `{` are blocks, so this is only reached inside an
+            // expression.
+            TokenType::LeftBrace => {
+                let brace = self.advance().unwrap();
+                let mut entries = Vec::new();
+                if !self.check(TokenType::RightBrace) {
+                    loop {
+                        let key = self.expr()?;
+                        self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+                        entries.push((key, self.expr()?));
+                        if self.matches(&[TokenType::Comma]).is_none() {
+                            break;
+                        }
+                    }
+                }
+                self.consume(TokenType::RightBrace, "Expect '}' after map entries.")?;
+                return Ok(Expr::Map { brace, entries });
+            }
+            TokenType::Super => {
+                let keyword = self.advance().unwrap();
+                self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+                let method =
+                    self.consume(TokenType::Identifier, "Expect superclass method name.")?;
+                return Ok(Expr::Super {
+                    id: next_expr_id(),
+                    keyword,
+                    method,
+                });
+            }
+            TokenType::This => {
+                let keyword = self.advance().unwrap();
+                return Ok(Expr::This {
+                    id: next_expr_id(),
+                    keyword,
+                });
+            }
+            TokenType::Identifier => {
+                let name = self.advance().unwrap();
+                return Ok(Expr::Variable {
+                    id: next_expr_id(),
+                    name,
+                });
+            }
+            TokenType::LeftParen => {
+                self.advance();
+                let expr = self.expr()?;
+                // Consume the closing paranthesis
+                self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+                return Ok(Expr::Grouping {
+                    expr: Box::new(expr),
+                });
+            }
+            _ => return Err(self.error_at_peek("Expect expression.")),
+        };
+        self.advance();
+        Ok(expr)
+    }
 
+    // An interpolated string, `"a ${b} c"`, becomes `"a " + <b as a string> + " c"`.
+    fn interpolation(&mut self) -> Result<Expr, CompileError> {
+        let mut parts = Vec::new();
+        loop {
+            let segment = self.advance().unwrap();
+            let text = segment.literal.clone().unwrap_or_default();
+            if !text.is_empty() {
+                parts.push((
+                    segment.clone(),
+                    Expr::Literal {
+                        value: LiteralValue::String(text),
+                        line: segment.line,
+                    },
+                ));
+            }
+            if segment.token_type == TokenType::String {
+                break;
+            }
 
+            let expr = self.expr()?;
+            parts.push((
+                segment,
+                Expr::Stringify {
+                    expr: Box::new(expr),
+                },
+            ));
+            // The rest of the string comes as a token starting at the closing brace.
+            let closed = self.peek().is_some_and(|t| {
+                matches!(t.token_type, TokenType::Interpolation | TokenType::String)
+                    && t.lexeme.starts_with('}')
+            });
+            if !closed {
+                return Err(self.error_at_peek("Expect '}' after interpolated expression."));
+            }
+        }
 
+        let mut parts = parts.into_iter();
+        // There is at least the expression after the first `${`.
+        let (_, mut expr) = parts.next().unwrap();
+        for (segment, part) in parts {
+            let op = Token {
+                token_type: TokenType::Plus,
+                lexeme: "+".to_string(),
+                line: segment.line,
+                column: segment.column,
+                literal: None,
+            };
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                op,
+                right: Box::new(part),
+            };
+        }
+        Ok(expr)
+    }
 
+    // Runs a grammar rule one level deeper, failing instead once nesting gets too deep.
+    fn nested<T>(
+        &mut self,
+        rule: fn(&mut Self) -> Result<T, CompileError>,
+    ) -> Result<T, CompileError> {
+        if self.depth >= MAX_NESTING {
+            return Err(self.error_at_peek("Too much nesting."));
+        }
+        self.depth += 1;
+        let result = rule(self);
+        self.depth -= 1;
+        result
+    }
+
+    // Peeks the next real token. Error tokens produced by the lexer are recorded and skipped
+    // here so that the grammar rules never see them.
+    fn peek(&mut self) -> Option<&Token> {
+        while let Some(t) = self.lexer.next_if(|t| t.token_type == TokenType::Error) {
+            self.errors.push(CompileError {
+                line: t.line,
+                column: t.column,
+                location: String::new(),
+                message: t.lexeme,
+            });
+        }
+        self.lexer.peek()
+    }
+
+    fn check(&mut self, token_type: TokenType) -> bool {
+        match self.peek() {
+            Some(t) => t.token_type == token_type,
+            None => false,
+        }
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        self.peek();
+        let token = self.lexer.next();
+        if token.is_some() {
+            self.previous = token.clone();
+        }
+        token
+    }
 
+    fn matches(&mut self, token_types: &[TokenType]) -> Option<Token> {
+        for token_type in token_types {
+            if self.check(*token_type) {
+                return self.advance();
+            }
+        }
+        None
+    }
 
+    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<Token, CompileError> {
+        if self.check(token_type) {
+            return Ok(self.advance().unwrap());
+        }
+        Err(self.error_at_peek(message))
+    }
 
+    fn error_at_peek(&mut self, message: &str) -> CompileError {
+        // Errors at the end of the input point just past the last token.
+        let (line, column) = self
+            .previous
+            .as_ref()
+            .map_or((1, 1), |t| (t.line, t.column + t.lexeme.chars().count()));
+        match self.peek() {
+            Some(t) => CompileError::at_token(t, message),
+            None => CompileError {
+                line,
+                column,
+                location: " at end".to_string(),
+                message: message.to_string(),
+            },
+        }
+    }
 
+    // Discards tokens until what is probably the start of the next statement.
+    fn synchronize(&mut self) {
+        while let Some(next) = self.advance() {
+            if next.token_type == TokenType::Semicolon {
+                return;
+            }
+            match self.peek().map(|t| t.token_type) {
+                Some(TokenType::Class)
+                | Some(TokenType::Fun)
+                | Some(TokenType::Var)
+                | Some(TokenType::For)
+                | Some(TokenType::If)
+                | Some(TokenType::While)
+                | Some(TokenType::Print)
+                | Some(TokenType::Return)
+                | Some(TokenType::Break)
+                | Some(TokenType::Continue)
+                | Some(TokenType::Throw)
+                | Some(TokenType::Try)
+                | Some(TokenType::Import)
+                | Some(TokenType::From) => return,
+                _ => {}
+            }
+        }
+    }
+}
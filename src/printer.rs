@@ -0,0 +1,381 @@
+use crate::lexer::TokenType;
+use crate::parser::Expr;
+use crate::parser::FunctionDecl;
+use crate::parser::LiteralValue;
+use crate::parser::Stmt;
+
+const INDENT: &str = "  ";
+
+// Binding strength of each expression form, loosest first. A child that binds more loosely
+// than its position requires is wrapped in parentheses.
+const ASSIGNMENT: u8 = 1;
+const OR: u8 = 2;
+const AND: u8 = 3;
+const EQUALITY: u8 = 4;
+const COMPARISON: u8 = 5;
+const TERM: u8 = 6;
+const FACTOR: u8 = 7;
+const UNARY: u8 = 8;
+const CALL: u8 = 9;
+const PRIMARY: u8 = 10;
+
+/// Prints an AST back out as Lox source. Parsing the output gives back an equivalent tree, so
+/// printing is idempotent: `print(parse(print(ast))) == print(ast)`. Comments are not part of
+/// the AST and are lost, and `for` loops come out with their initializer in a block around the loop.
+pub fn print_program(statements: &[Stmt]) -> String {
+    let mut out = String::new();
+    for statement in statements {
+        print_stmt(statement, 0, &mut out);
+    }
+    out
+}
+
+pub fn print_expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Literal { value, .. } => print_literal(value),
+        Expr::Grouping { expr } => format!("({})", print_expr(expr)),
+        Expr::Stringify { expr } => format!("\"${{{}}}\"", print_expr(expr)),
+        Expr::Variable { name, .. } => name.lexeme.clone(),
+        Expr::This { .. } => "this".to_string(),
+        Expr::Super { method, .. } => format!("super.{}", method.lexeme),
+        Expr::Assign { name, value, .. } => {
+            format!("{} = {}", name.lexeme, print_operand(value, ASSIGNMENT))
+        }
+        Expr::Set {
+            object,
+            name,
+            value,
+        } => format!(
+            "{}.{} = {}",
+            print_operand(object, CALL),
+            name.lexeme,
+            print_operand(value, ASSIGNMENT)
+        ),
+        Expr::Binary { left, op, right } | Expr::Logical { left, op, right } => {
+            let precedence = precedence(expr);
+            // Operators are left associative, so only the right side needs to bind tighter.
+            format!(
+                "{} {} {}",
+                print_operand(left, precedence),
+                op.lexeme,
+                print_operand(right, precedence + 1)
+            )
+        }
+        Expr::Unary { op, right } => format!("{}{}", op.lexeme, print_operand(right, UNARY)),
+        Expr::Call {
+            callee, arguments, ..
+        } => {
+            let arguments: Vec<String> = arguments
+                .iter()
+                .map(|argument| print_operand(argument, ASSIGNMENT))
+                .collect();
+            format!("{}({})", print_operand(callee, CALL), arguments.join(", "))
+        }
+        Expr::Get { object, name } => format!("{}.{}", print_operand(object, CALL), name.lexeme),
+        Expr::List { elements, .. } => {
+            let elements: Vec<String> = elements
+                .iter()
+                .map(|element| print_operand(element, ASSIGNMENT))
+                .collect();
+            format!("[{}]", elements.join(", "))
+        }
+        Expr::Map { entries, .. } => {
+            let entries: Vec<String> = entries
+                .iter()
+                .map(|(key, value)| {
+                    format!(
+                        "{}: {}",
+                        print_operand(key, ASSIGNMENT),
+                        print_operand(value, ASSIGNMENT)
+                    )
+                })
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        Expr::Index { object, index, .. } => {
+            format!("{}[{}]", print_operand(object, CALL), print_expr(index))
+        }
+        Expr::SetIndex {
+            object,
+            index,
+            value,
+            ..
+        } => format!(
+            "{}[{}] = {}",
+            print_operand(object, CALL),
+            print_expr(index),
+            print_operand(value, ASSIGNMENT)
+        ),
+    }
+}
+
+fn print_operand(expr: &Expr, min_precedence: u8) -> String {
+    if precedence(expr) < min_precedence {
+        format!("({})", print_expr(expr))
+    } else {
+        print_expr(expr)
+    }
+}
+
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Assign { .. } | Expr::Set { .. } | Expr::SetIndex { .. } => ASSIGNMENT,
+        Expr::Logical { op, .. } if op.token_type == TokenType::Or => OR,
+        Expr::Logical { .. } => AND,
+        Expr::Binary { op, .. } => match op.token_type {
+            TokenType::EqualEqual | TokenType::BangEqual => EQUALITY,
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => COMPARISON,
+            TokenType::Plus | TokenType::Minus => TERM,
+            _ => FACTOR,
+        },
+        Expr::Unary { .. } => UNARY,
+        // A negative number prints with a leading minus, which binds like a unary operator.
+        Expr::Literal {
+            value: LiteralValue::Number(n),
+            ..
+        } if n.is_sign_negative() => UNARY,
+        Expr::Call { .. } | Expr::Get { .. } | Expr::Index { .. } => CALL,
+        Expr::Literal { .. }
+        | Expr::Grouping { .. }
+        | Expr::Stringify { .. }
+        | Expr::List { .. }
+        | Expr::Map { .. }
+        | Expr::Variable { .. }
+        | Expr::This { .. }
+        | Expr::Super { .. } => PRIMARY,
+    }
+}
+
+// Whether the printed expression begins with a map literal's `{`.
+fn starts_with_map(expr: &Expr) -> bool {
+    match expr {
+        Expr::Map { .. } => true,
+        Expr::Binary { left, .. } | Expr::Logical { left, .. } => {
+            starts_with_map(left) && precedence(left) >= precedence(expr)
+        }
+        Expr::Call { callee: object, .. }
+        | Expr::Get { object, .. }
+        | Expr::Set { object, .. }
+        | Expr::Index { object, .. }
+        | Expr::SetIndex { object, .. } => starts_with_map(object) && precedence(object) >= CALL,
+        _ => false,
+    }
+}
+
+fn print_literal(value: &LiteralValue) -> String {
+    match value {
+        LiteralValue::Nil => "nil".to_string(),
+        LiteralValue::Bool(b) => b.to_string(),
+        LiteralValue::Number(n) => n.to_string(),
+        LiteralValue::String(s) => {
+            let mut out = String::from("\"");
+            let mut chars = s.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    // Would start an interpolation.
+                    '$' if chars.peek() == Some(&'{') => out.push_str("\\$"),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\t' => out.push_str("\\t"),
+                    c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+            out
+        }
+    }
+}
+
+fn indent(level: usize, out: &mut String) {
+    for _ in 0..level {
+        out.push_str(INDENT);
+    }
+}
+
+fn print_stmt(stmt: &Stmt, level: usize, out: &mut String) {
+    indent(level, out);
+    print_stmt_inline(stmt, level, out);
+    out.push('\n');
+}
+
+// Prints a statement starting at the current position, with nested lines indented under
+// `level`. No trailing newline.
+fn print_stmt_inline(stmt: &Stmt, level: usize, out: &mut String) {
+    match stmt {
+        // A statement starting with `{` would parse as a block.
+        Stmt::Expression { expr } if starts_with_map(expr) => {
+            out.push_str(&format!("({});", print_expr(expr)));
+        }
+        Stmt::Expression { expr } => {
+            out.push_str(&print_expr(expr));
+            out.push(';');
+        }
+        Stmt::Print { expr } => {
+            out.push_str("print ");
+            out.push_str(&print_expr(expr));
+            out.push(';');
+        }
+        Stmt::Var { name, initializer } => {
+            out.push_str("var ");
+            out.push_str(&name.lexeme);
+            if let Some(initializer) = initializer {
+                out.push_str(" = ");
+                out.push_str(&print_expr(initializer));
+            }
+            out.push(';');
+        }
+        Stmt::Block { statements } => print_block(statements, level, out),
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            out.push_str("if (");
+            out.push_str(&print_expr(condition));
+            out.push_str(") ");
+            // An else after an inner if without one would be taken as the inner if's else.
+            let dangling = else_branch.is_some()
+                && matches!(**then_branch, Stmt::If { ref else_branch, .. } if else_branch.is_none());
+            if dangling {
+                print_block(std::slice::from_ref(then_branch), level, out);
+            } else {
+                print_stmt_inline(then_branch, level, out);
+            }
+            if let Some(else_branch) = else_branch {
+                out.push_str(" else ");
+                print_stmt_inline(else_branch, level, out);
+            }
+        }
+        Stmt::While {
+            condition,
+            body,
+            increment: None,
+        } => {
+            out.push_str("while (");
+            out.push_str(&print_expr(condition));
+            out.push_str(") ");
+            print_stmt_inline(body, level, out);
+        }
+        Stmt::While {
+            condition,
+            body,
+            increment: Some(increment),
+        } => {
+            out.push_str(&format!(
+                "for (; {}; {}) ",
+                print_expr(condition),
+                print_expr(increment)
+            ));
+            print_stmt_inline(body, level, out);
+        }
+        Stmt::Throw { value, .. } => {
+            out.push_str("throw ");
+            out.push_str(&print_expr(value));
+            out.push(';');
+        }
+        Stmt::Try {
+            body,
+            catch,
+            finally,
+        } => {
+            out.push_str("try ");
+            print_block(body, level, out);
+            if let Some((name, handler)) = catch {
+                out.push_str(&format!(" catch ({}) ", name.lexeme));
+                print_block(handler, level, out);
+            }
+            if let Some(finally) = finally {
+                out.push_str(" finally ");
+                print_block(finally, level, out);
+            }
+        }
+        Stmt::Import {
+            path, alias, names, ..
+        } => match alias {
+            Some(alias) => out.push_str(&format!("import {} as {};", path.lexeme, alias.lexeme)),
+            None => {
+                let names: Vec<&str> = names.iter().map(|name| name.lexeme.as_str()).collect();
+                out.push_str(&format!(
+                    "from {} import {};",
+                    path.lexeme,
+                    names.join(", ")
+                ));
+            }
+        },
+        Stmt::Break { .. } => out.push_str("break;"),
+        Stmt::Continue { .. } => out.push_str("continue;"),
+        Stmt::ForIn {
+            name,
+            iterable,
+            body,
+        } => {
+            out.push_str(&format!("for (var {} in ", name.lexeme));
+            out.push_str(&print_expr(iterable));
+            out.push_str(") ");
+            print_stmt_inline(body, level, out);
+        }
+        Stmt::Function { decl } => {
+            out.push_str("fun ");
+            print_function(decl, level, out);
+        }
+        Stmt::Return { value, .. } => {
+            out.push_str("return");
+            if let Some(value) = value {
+                out.push(' ');
+                out.push_str(&print_expr(value));
+            }
+            out.push(';');
+        }
+        Stmt::Class {
+            name,
+            superclass,
+            methods,
+        } => {
+            out.push_str("class ");
+            out.push_str(&name.lexeme);
+            if let Some(superclass) = superclass {
+                out.push_str(" < ");
+                out.push_str(&print_expr(superclass));
+            }
+            if methods.is_empty() {
+                out.push_str(" {}");
+                return;
+            }
+            out.push_str(" {\n");
+            for method in methods {
+                indent(level + 1, out);
+                print_function(method, level + 1, out);
+                out.push('\n');
+            }
+            indent(level, out);
+            out.push('}');
+        }
+    }
+}
+
+fn print_function(decl: &FunctionDecl, level: usize, out: &mut String) {
+    let params: Vec<&str> = decl.params.iter().map(|p| p.lexeme.as_str()).collect();
+    out.push_str(&decl.name.lexeme);
+    out.push('(');
+    out.push_str(&params.join(", "));
+    out.push_str(") ");
+    print_block(&decl.body, level, out);
+}
+
+fn print_block(statements: &[Stmt], level: usize, out: &mut String) {
+    if statements.is_empty() {
+        out.push_str("{}");
+        return;
+    }
+    out.push_str("{\n");
+    for statement in statements {
+        print_stmt(statement, level + 1, out);
+    }
+    indent(level, out);
+    out.push('}');
+}
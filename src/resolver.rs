@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::lexer::Token;
+use crate::parser::CompileError;
+use crate::parser::Expr;
+use crate::parser::FunctionDecl;
+use crate::parser::Stmt;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// Walks the AST once before it runs and works out, for every local variable reference, how
+/// many scopes separate it from its declaration. References that are not found in any scope
+/// are left out and treated as globals by the interpreter.
+pub struct Resolver {
+    // Each scope maps a name to whether its initializer has finished.
+    scopes: Vec<HashMap<String, bool>>,
+    locals: HashMap<usize, usize>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    // How many loops enclose the current statement within its function.
+    loop_depth: usize,
+    errors: Vec<CompileError>,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            scopes: Vec::new(),
+            locals: HashMap::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            loop_depth: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Returns a map from expression id to scope distance.
+    pub fn resolve(
+        mut self,
+        statements: &[Stmt],
+    ) -> Result<HashMap<usize, usize>, Vec<CompileError>> {
+        self.resolve_statements(statements);
+        if self.errors.is_empty() {
+            Ok(self.locals)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn resolve_statements(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.resolve_stmt(statement);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block { statements } => {
+                self.begin_scope();
+                self.resolve_statements(statements);
+                self.end_scope();
+            }
+            Stmt::Var { name, initializer } => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expr(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::Function { decl } => {
+                self.declare(&decl.name);
+                self.define(&decl.name);
+                self.resolve_function(decl, FunctionType::Function);
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => self.resolve_class(name, superclass.as_ref(), methods),
+            Stmt::Expression { expr } | Stmt::Print { expr } => self.resolve_expr(expr),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            Stmt::Return { keyword, value } => {
+                if self.current_function == FunctionType::None {
+                    self.error(keyword, "Can't return from top-level code.");
+                }
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        self.error(keyword, "Can't return a value from an initializer.");
+                    }
+                    self.resolve_expr(value);
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => {
+                self.resolve_expr(condition);
+                self.loop_depth += 1;
+                self.resolve_stmt(body);
+                self.loop_depth -= 1;
+                if let Some(increment) = increment {
+                    self.resolve_expr(increment);
+                }
+            }
+            Stmt::Break { keyword } if self.loop_depth == 0 => {
+                self.error(keyword, "Can't use 'break' outside of a loop.");
+            }
+            Stmt::Continue { keyword } if self.loop_depth == 0 => {
+                self.error(keyword, "Can't use 'continue' outside of a loop.");
+            }
+            Stmt::Break { .. } | Stmt::Continue { .. } => {}
+            Stmt::Throw { value, .. } => self.resolve_expr(value),
+            Stmt::Import { alias, names, .. } => {
+                for name in alias.iter().chain(names) {
+                    self.declare(name);
+                    self.define(name);
+                }
+            }
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.begin_scope();
+                self.resolve_statements(body);
+                self.end_scope();
+                // The error variable lives in the same scope as the catch body.
+                if let Some((name, handler)) = catch {
+                    self.begin_scope();
+                    self.declare(name);
+                    self.define(name);
+                    self.resolve_statements(handler);
+                    self.end_scope();
+                }
+                if let Some(finally) = finally {
+                    self.begin_scope();
+                    self.resolve_statements(finally);
+                    self.end_scope();
+                }
+            }
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => {
+                self.resolve_expr(iterable);
+                self.begin_scope();
+                self.declare(name);
+                self.define(name);
+                self.loop_depth += 1;
+                self.resolve_stmt(body);
+                self.loop_depth -= 1;
+                self.end_scope();
+            }
+        }
+    }
+
+    fn resolve_class(
+        &mut self,
+        name: &Token,
+        superclass: Option<&Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+
+        self.declare(name);
+        self.define(name);
+
+        if let Some(superclass) = superclass {
+            if let Expr::Variable {
+                name: superclass_name,
+                ..
+            } = superclass
+                && superclass_name.lexeme == name.lexeme
+            {
+                self.error(superclass_name, "A class can't inherit from itself.");
+            }
+            self.current_class = ClassType::Subclass;
+            self.resolve_expr(superclass);
+
+            self.begin_scope();
+            self.scopes
+                .last_mut()
+                .unwrap()
+                .insert("super".to_string(), true);
+        }
+
+        self.begin_scope();
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert("this".to_string(), true);
+
+        for method in methods {
+            let function_type = if method.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(method, function_type);
+        }
+
+        self.end_scope();
+        if superclass.is_some() {
+            self.end_scope();
+        }
+
+        self.current_class = enclosing_class;
+    }
+
+    fn resolve_function(&mut self, decl: &FunctionDecl, function_type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+        // A loop around the declaration doesn't make `break` valid inside the body.
+        let enclosing_loops = std::mem::take(&mut self.loop_depth);
+
+        self.begin_scope();
+        for param in &decl.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_statements(&decl.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+        self.loop_depth = enclosing_loops;
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Variable { id, name } => {
+                if let Some(scope) = self.scopes.last()
+                    && scope.get(&name.lexeme) == Some(&false)
+                {
+                    self.error(name, "Can't read local variable in its own initializer.");
+                }
+                self.resolve_local(*id, name);
+            }
+            Expr::Assign { id, name, value } => {
+                self.resolve_expr(value);
+                self.resolve_local(*id, name);
+            }
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.resolve_expr(callee);
+                for argument in arguments {
+                    self.resolve_expr(argument);
+                }
+            }
+            Expr::Get { object, .. } => self.resolve_expr(object),
+            Expr::Set { object, value, .. } => {
+                self.resolve_expr(value);
+                self.resolve_expr(object);
+            }
+            Expr::List { elements, .. } => {
+                for element in elements {
+                    self.resolve_expr(element);
+                }
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.resolve_expr(key);
+                    self.resolve_expr(value);
+                }
+            }
+            Expr::Index { object, index, .. } => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+                self.resolve_expr(value);
+            }
+            Expr::Grouping { expr } | Expr::Stringify { expr } => self.resolve_expr(expr),
+            Expr::Literal { .. } => {}
+            Expr::Unary { right, .. } => self.resolve_expr(right),
+            Expr::This { id, keyword } => {
+                if self.current_class == ClassType::None {
+                    self.error(keyword, "Can't use 'this' outside of a class.");
+                    return;
+                }
+                self.resolve_local(*id, keyword);
+            }
+            Expr::Super { id, keyword, .. } => {
+                if self.current_class == ClassType::None {
+                    self.error(keyword, "Can't use 'super' outside of a class.");
+                } else if self.current_class != ClassType::Subclass {
+                    self.error(keyword, "Can't use 'super' in a class with no superclass.");
+                }
+                self.resolve_local(*id, keyword);
+            }
+        }
+    }
+
+    fn resolve_local(&mut self, id: usize, name: &Token) {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(&name.lexeme) {
+                self.locals.insert(id, depth);
+                return;
+            }
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if scope.contains_key(&name.lexeme) {
+            self.error(name, "Already a variable with this name in this scope.");
+            return;
+        }
+        scope.insert(name.lexeme.clone(), false);
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        self.errors.push(CompileError::at_token(token, message));
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+
+use crate::lexer::Token;
+use crate::parser::Expr;
+use crate::parser::FunctionDecl;
+use crate::parser::Stmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeclarationKind {
+    Variable,
+    Parameter,
+    Function,
+    Class,
+    Method,
+}
+
+#[derive(Debug, Clone)]
+pub struct Declaration {
+    pub name: Token,
+    pub kind: DeclarationKind,
+    /// A one line summary, like `fun add(a, b)` or `(parameter) a`.
+    pub detail: String,
+    /// Whether the declaration is at the top level of the script.
+    pub global: bool,
+}
+
+/// Links every use of a name in a program to the declaration it refers to. Uses of names
+/// that are never declared, like the native functions, are left out. Fields are dynamic so
+/// only `this.method` accesses inside a class are linked to its methods.
+#[derive(Debug, Default)]
+pub struct SymbolIndex {
+    pub declarations: Vec<Declaration>,
+    /// The name token of each use, with the index of its declaration.
+    pub references: Vec<(Token, usize)>,
+}
+
+impl SymbolIndex {
+    pub fn build(statements: &[Stmt]) -> SymbolIndex {
+        let mut indexer = Indexer::default();
+        indexer.statements(statements);
+
+        // Globals can be used before they are declared, as long as that happens at runtime
+        // after the declaration, so they are linked once everything has been seen.
+        for name in std::mem::take(&mut indexer.unresolved) {
+            if let Some(&declaration) = indexer.globals.get(&name.lexeme) {
+                indexer.index.references.push((name, declaration));
+            }
+        }
+        indexer.index
+    }
+
+    /// The declaration of the name at a position, whether the position is on the declaration
+    /// itself or on one of its uses. Lines and columns count from 1.
+    pub fn declaration_at(&self, line: usize, column: usize) -> Option<usize> {
+        let covers = |token: &Token| {
+            token.line == line
+                && column >= token.column
+                && column < token.column + token.lexeme.chars().count()
+        };
+        self.declarations
+            .iter()
+            .position(|d| covers(&d.name))
+            .or_else(|| {
+                self.references
+                    .iter()
+                    .find(|(token, _)| covers(token))
+                    .map(|(_, declaration)| *declaration)
+            })
+    }
+
+    pub fn references_to(&self, declaration: usize) -> impl Iterator<Item = &Token> {
+        self.references
+            .iter()
+            .filter(move |(_, d)| *d == declaration)
+            .map(|(token, _)| token)
+    }
+}
+
+#[derive(Default)]
+struct Indexer {
+    index: SymbolIndex,
+    // Local scopes, innermost last. Top level declarations go into `globals`.
+    scopes: Vec<HashMap<String, usize>>,
+    globals: HashMap<String, usize>,
+    unresolved: Vec<Token>,
+    // The methods of each class being indexed, innermost last.
+    classes: Vec<HashMap<String, usize>>,
+}
+
+impl Indexer {
+    fn add(&mut self, name: &Token, kind: DeclarationKind, detail: String) -> usize {
+        self.index.declarations.push(Declaration {
+            name: name.clone(),
+            kind,
+            detail,
+            global: self.scopes.is_empty(),
+        });
+        self.index.declarations.len() - 1
+    }
+
+    fn declare(&mut self, name: &Token, kind: DeclarationKind, detail: String) {
+        let declaration = self.add(name, kind, detail);
+        let scope = self.scopes.last_mut().unwrap_or(&mut self.globals);
+        scope.insert(name.lexeme.clone(), declaration);
+    }
+
+    fn reference(&mut self, name: &Token) {
+        let local = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.lexeme).copied());
+        match local {
+            Some(declaration) => self.index.references.push((name.clone(), declaration)),
+            None => self.unresolved.push(name.clone()),
+        }
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Expression { expr } | Stmt::Print { expr } => self.expr(expr),
+            Stmt::Var { name, initializer } => {
+                if let Some(initializer) = initializer {
+                    self.expr(initializer);
+                }
+                self.declare(
+                    name,
+                    DeclarationKind::Variable,
+                    format!("var {}", name.lexeme),
+                );
+            }
+            Stmt::Block { statements } => {
+                self.scopes.push(HashMap::new());
+                self.statements(statements);
+                self.scopes.pop();
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expr(condition);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => {
+                self.expr(condition);
+                self.statement(body);
+                if let Some(increment) = increment {
+                    self.expr(increment);
+                }
+            }
+            Stmt::Break { .. } | Stmt::Continue { .. } => {}
+            Stmt::Throw { value, .. } => self.expr(value),
+            Stmt::Import {
+                path, alias, names, ..
+            } => {
+                if let Some(alias) = alias {
+                    let detail = format!("import {} as {}", path.lexeme, alias.lexeme);
+                    self.declare(alias, DeclarationKind::Variable, detail);
+                }
+                for name in names {
+                    let detail = format!("from {} import {}", path.lexeme, name.lexeme);
+                    self.declare(name, DeclarationKind::Variable, detail);
+                }
+            }
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.scopes.push(HashMap::new());
+                self.statements(body);
+                self.scopes.pop();
+                if let Some((name, handler)) = catch {
+                    self.scopes.push(HashMap::new());
+                    let detail = format!("catch ({})", name.lexeme);
+                    self.declare(name, DeclarationKind::Parameter, detail);
+                    self.statements(handler);
+                    self.scopes.pop();
+                }
+                if let Some(finally) = finally {
+                    self.scopes.push(HashMap::new());
+                    self.statements(finally);
+                    self.scopes.pop();
+                }
+            }
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => {
+                self.expr(iterable);
+                self.scopes.push(HashMap::new());
+                self.declare(
+                    name,
+                    DeclarationKind::Variable,
+                    format!("var {}", name.lexeme),
+                );
+                self.statement(body);
+                self.scopes.pop();
+            }
+            Stmt::Function { decl } => {
+                let detail = format!("fun {}", signature(decl));
+                self.declare(&decl.name, DeclarationKind::Function, detail);
+                self.function(decl);
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let detail = match superclass {
+                    Some(Expr::Variable {
+                        name: superclass, ..
+                    }) => format!("class {} < {}", name.lexeme, superclass.lexeme),
+                    _ => format!("class {}", name.lexeme),
+                };
+                self.declare(name, DeclarationKind::Class, detail);
+                if let Some(superclass) = superclass {
+                    self.expr(superclass);
+                }
+
+                let mut method_declarations = HashMap::new();
+                for method in methods {
+                    let detail = format!("(method) {}.{}", name.lexeme, signature(method));
+                    let declaration = self.add(&method.name, DeclarationKind::Method, detail);
+                    method_declarations.insert(method.name.lexeme.clone(), declaration);
+                }
+                self.classes.push(method_declarations);
+                for method in methods {
+                    self.function(method);
+                }
+                self.classes.pop();
+            }
+        }
+    }
+
+    fn function(&mut self, decl: &FunctionDecl) {
+        self.scopes.push(HashMap::new());
+        for param in &decl.params {
+            let detail = format!("(parameter) {}", param.lexeme);
+            self.declare(param, DeclarationKind::Parameter, detail);
+        }
+        self.statements(&decl.body);
+        self.scopes.pop();
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Variable { name, .. } => self.reference(name),
+            Expr::Assign { name, value, .. } => {
+                self.expr(value);
+                self.reference(name);
+            }
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Unary { right, .. } => self.expr(right),
+            Expr::Grouping { expr } | Expr::Stringify { expr } => self.expr(expr),
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.expr(callee);
+                for argument in arguments {
+                    self.expr(argument);
+                }
+            }
+            Expr::Get { object, name } => {
+                self.expr(object);
+                if matches!(**object, Expr::This { .. }) {
+                    let method = self.classes.last().and_then(|c| c.get(&name.lexeme));
+                    if let Some(&declaration) = method {
+                        self.index.references.push((name.clone(), declaration));
+                    }
+                }
+            }
+            Expr::Set { object, value, .. } => {
+                self.expr(object);
+                self.expr(value);
+            }
+            Expr::List { elements, .. } => {
+                for element in elements {
+                    self.expr(element);
+                }
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            }
+            Expr::Index { object, index, .. } => {
+                self.expr(object);
+                self.expr(index);
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.expr(object);
+                self.expr(index);
+                self.expr(value);
+            }
+            Expr::Literal { .. } | Expr::This { .. } | Expr::Super { .. } => {}
+        }
+    }
+}
+
+fn signature(decl: &FunctionDecl) -> String {
+    let params: Vec<&str> = decl.params.iter().map(|p| p.lexeme.as_str()).collect();
+    format!("{}({})", decl.name.lexeme, params.join(", "))
+}
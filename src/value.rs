@@ -0,0 +1,470 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Formatter;
+use std::rc::Rc;
+
+use crate::environment::Environment;
+use crate::interpreter::Interpreter;
+use crate::interpreter::StackFrame;
+use crate::interpreter::Unwind;
+use crate::parser::FunctionDecl;
+
+#[derive(Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Rc<str>),
+    Function(Rc<LoxFunction>),
+    Native(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<LoxMap>>),
+    Range(LoxRange),
+    Error(Rc<LoxError>),
+    Module(Rc<LoxModule>),
+}
+
+impl Value {
+    /// Lox follows Ruby: only nil and false are falsey.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Function(_) | Value::Native(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Range(_) => "range",
+            Value::Error(_) => "error",
+            Value::Module(_) => "module",
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Native(a), Value::Native(b)) => Rc::ptr_eq(a, b),
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            (Value::List(a), Value::List(b)) => Rc::ptr_eq(a, b),
+            (Value::Map(a), Value::Map(b)) => Rc::ptr_eq(a, b),
+            (Value::Range(a), Value::Range(b)) => a == b,
+            (Value::Error(a), Value::Error(b)) => Rc::ptr_eq(a, b),
+            (Value::Module(a), Value::Module(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Function(function) => write!(f, "<fn {}>", function.declaration.name.lexeme),
+            Value::Native(_) => write!(f, "<native fn>"),
+            Value::Class(class) => write!(f, "{}", class.name),
+            Value::Instance(instance) => write!(f, "{} instance", instance.borrow().class.name),
+            Value::List(list) => write_collection(f, Rc::as_ptr(list) as *const (), "[...]", |f| {
+                write_elements(f, &list.borrow())
+            }),
+            Value::Map(map) => write_collection(f, Rc::as_ptr(map) as *const (), "{...}", |f| {
+                write_entries(f, &map.borrow())
+            }),
+            Value::Range(range) => write!(f, "range({}, {})", range.start, range.end),
+            Value::Error(error) => write!(f, "{}", error.message),
+            Value::Module(module) => write!(f, "<module {}>", module.name),
+        }
+    }
+}
+
+thread_local! {
+    // The collections being printed, innermost last.
+    static PRINTING: RefCell<Vec<*const ()>> = const { RefCell::new(Vec::new()) };
+}
+
+// Writes a collection with `write`, or `placeholder` if it is already being written further
+// out, so a collection that contains itself doesn't print forever.
+fn write_collection(
+    f: &mut Formatter<'_>,
+    address: *const (),
+    placeholder: &str,
+    write: impl FnOnce(&mut Formatter<'_>) -> Result<(), std::fmt::Error>,
+) -> Result<(), std::fmt::Error> {
+    if PRINTING.with_borrow(|printing| printing.contains(&address)) {
+        return write!(f, "{}", placeholder);
+    }
+    PRINTING.with_borrow_mut(|printing| printing.push(address));
+    let result = write(f);
+    PRINTING.with_borrow_mut(|printing| printing.pop());
+    result
+}
+
+// Writes list elements between brackets, with strings quoted.
+fn write_elements(f: &mut Formatter<'_>, elements: &[Value]) -> Result<(), std::fmt::Error> {
+    write!(f, "[")?;
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{:?}", element)?;
+    }
+    write!(f, "]")
+}
+
+// Writes map entries between braces, with strings quoted.
+fn write_entries(f: &mut Formatter<'_>, map: &LoxMap) -> Result<(), std::fmt::Error> {
+    write!(f, "{{")?;
+    for (i, (key, value)) in map.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{:?}: {:?}", key.to_value(), value)?;
+    }
+    write!(f, "}}")
+}
+
+impl std::fmt::Debug for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Value::String(s) => write!(f, "{:?}", s),
+            other => write!(f, "{}", other),
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Value {
+        Value::Number(n)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(n: i32) -> Value {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(Rc::from(s))
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(Rc::from(s))
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(elements: Vec<Value>) -> Value {
+        Value::List(Rc::new(RefCell::new(elements)))
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Value {
+        Value::Nil
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(option: Option<T>) -> Value {
+        option.map_or(Value::Nil, Into::into)
+    }
+}
+
+/// Returned when a Lox value can't be converted into the requested Rust type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeMismatch {
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
+impl std::fmt::Display for TypeMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "Expected a {} but got a {}.", self.expected, self.found)
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = TypeMismatch;
+    fn try_from(value: Value) -> Result<bool, TypeMismatch> {
+        match value {
+            Value::Bool(b) => Ok(b),
+            other => Err(TypeMismatch {
+                expected: "boolean",
+                found: other.type_name(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = TypeMismatch;
+    fn try_from(value: Value) -> Result<f64, TypeMismatch> {
+        match value {
+            Value::Number(n) => Ok(n),
+            other => Err(TypeMismatch {
+                expected: "number",
+                found: other.type_name(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = TypeMismatch;
+    fn try_from(value: Value) -> Result<String, TypeMismatch> {
+        match value {
+            Value::String(s) => Ok(s.to_string()),
+            other => Err(TypeMismatch {
+                expected: "string",
+                found: other.type_name(),
+            }),
+        }
+    }
+}
+
+/// Copies the elements out of a list.
+impl TryFrom<Value> for Vec<Value> {
+    type Error = TypeMismatch;
+    fn try_from(value: Value) -> Result<Vec<Value>, TypeMismatch> {
+        match value {
+            Value::List(list) => Ok(list.borrow().clone()),
+            other => Err(TypeMismatch {
+                expected: "list",
+                found: other.type_name(),
+            }),
+        }
+    }
+}
+
+/// What a `catch` clause receives: a runtime error, or a value thrown with `throw`.
+pub struct LoxError {
+    pub message: String,
+    pub line: usize,
+    /// The thrown value, or the message of a runtime error.
+    pub value: Value,
+    /// Where the error was raised, innermost call first.
+    pub trace: Vec<StackFrame>,
+}
+
+/// A file loaded with `import`. Its top level declarations are its exports, read live from
+/// the module's own globals.
+pub struct LoxModule {
+    pub name: String,
+    pub globals: Rc<RefCell<Environment>>,
+    pub exports: Vec<String>,
+}
+
+impl LoxModule {
+    pub fn get(&self, name: &str) -> Option<Value> {
+        if !self.exports.iter().any(|export| export == name) {
+            return None;
+        }
+        self.globals.borrow().get_value(name)
+    }
+}
+
+/// The numbers from `start` up to but not including `end`, counting by one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoxRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl LoxRange {
+    pub fn len(&self) -> usize {
+        (self.end - self.start).ceil().max(0.0) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A value that can be used as a map key. Only values compared by content can be keys, so
+/// collections, functions and instances can't.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Nil,
+    Bool(bool),
+    // The bits of the number, with negative zero turned into zero since they are equal.
+    Number(u64),
+    String(Rc<str>),
+}
+
+impl MapKey {
+    /// The key for `value`, or None when it can't be a key.
+    pub fn new(value: &Value) -> Option<MapKey> {
+        Some(match value {
+            Value::Nil => MapKey::Nil,
+            Value::Bool(b) => MapKey::Bool(*b),
+            Value::Number(n) => MapKey::Number(if *n == 0.0 { 0.0f64 } else { *n }.to_bits()),
+            Value::String(s) => MapKey::String(Rc::clone(s)),
+            _ => return None,
+        })
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            MapKey::Nil => Value::Nil,
+            MapKey::Bool(b) => Value::Bool(*b),
+            MapKey::Number(bits) => Value::Number(f64::from_bits(*bits)),
+            MapKey::String(s) => Value::String(Rc::clone(s)),
+        }
+    }
+}
+
+/// The entries of a map, kept in the order their keys were first inserted.
+#[derive(Default)]
+pub struct LoxMap {
+    entries: Vec<(MapKey, Value)>,
+    positions: HashMap<MapKey, usize>,
+}
+
+impl LoxMap {
+    pub fn get(&self, key: &MapKey) -> Option<&Value> {
+        self.positions.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn contains(&self, key: &MapKey) -> bool {
+        self.positions.contains_key(key)
+    }
+
+    /// Sets the value for a key. A key that was already there keeps its place. Returns
+    /// whether the key is new.
+    pub fn insert(&mut self, key: MapKey, value: Value) -> bool {
+        if let Some(&i) = self.positions.get(&key) {
+            self.entries[i].1 = value;
+            return false;
+        }
+        self.positions.insert(key.clone(), self.entries.len());
+        self.entries.push((key, value));
+        true
+    }
+
+    pub fn remove(&mut self, key: &MapKey) -> Option<Value> {
+        let i = self.positions.remove(key)?;
+        let (_, value) = self.entries.remove(i);
+        for (key, _) in &self.entries[i..] {
+            *self.positions.get_mut(key).unwrap() -= 1;
+        }
+        Some(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&MapKey, &Value)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+pub struct LoxFunction {
+    pub declaration: Rc<FunctionDecl>,
+    pub closure: Rc<RefCell<Environment>>,
+    /// Scope distances from the resolver run that saw the declaration.
+    pub locals: Rc<HashMap<usize, usize>>,
+    pub is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    /// Returns a copy of this method whose closure has `this` bound to `instance`.
+    pub fn bind(&self, instance: Value) -> LoxFunction {
+        let environment = Environment::new(Some(Rc::clone(&self.closure)));
+        environment.borrow_mut().define("this", instance);
+        LoxFunction {
+            declaration: Rc::clone(&self.declaration),
+            closure: environment,
+            locals: Rc::clone(&self.locals),
+            is_initializer: self.is_initializer,
+        }
+    }
+}
+
+pub type NativeFn = dyn Fn(&mut Interpreter, &[Value]) -> Result<Value, Unwind>;
+
+/// A function implemented in Rust and callable from Lox.
+///
+/// Errors returned by the closure may use line 0, the interpreter fills in the line of the
+/// call site.
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new<F>(name: &str, arity: usize, function: F) -> NativeFunction
+    where
+        F: Fn(&mut Interpreter, &[Value]) -> Result<Value, Unwind> + 'static,
+    {
+        NativeFunction {
+            name: name.to_string(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+}
+
+pub struct LoxClass {
+    pub name: String,
+    pub superclass: Option<Rc<LoxClass>>,
+    pub methods: HashMap<String, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        match self.methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self
+                .superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name)),
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.find_method("init").map_or(0, |init| init.arity())
+    }
+}
+
+pub struct LoxInstance {
+    pub class: Rc<LoxClass>,
+    pub fields: HashMap<String, Value>,
+}
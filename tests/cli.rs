@@ -0,0 +1,66 @@
+use std::env;
+use std::fs;
+use std::process::Command;
+
+// The exit code, stdout and stderr of running rox with `args`.
+fn rox(args: &[&str]) -> (Option<i32>, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_rox"))
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+fn script(name: &str, source: &str) -> String {
+    let path = env::temp_dir().join(format!("rox-cli-{}-{}.lox", std::process::id(), name));
+    fs::write(&path, source).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn timeout_interrupts_the_script() {
+    let path = script("timeout", "while (true) {}\n");
+    let (code, _, stderr) = rox(&["run", "--timeout", "0.1", &path]);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(code, Some(70));
+    assert_eq!(stderr.lines().next(), Some("Interrupted."));
+}
+
+#[test]
+fn timeout_of_zero_still_interrupts() {
+    let path = script("timeout-zero", "while (true) {}\n");
+    let (code, _, stderr) = rox(&["run", "--timeout", "0", &path]);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(code, Some(70));
+    assert_eq!(stderr.lines().next(), Some("Interrupted."));
+}
+
+#[test]
+fn deep_recursion_reports_a_stack_overflow() {
+    let path = script("depth", "fun recurse() { recurse(); }\nrecurse();\n");
+    let (code, _, stderr) = rox(&["run", "--max-depth", "200000", &path]);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(code, Some(70));
+    assert_eq!(stderr.lines().next(), Some("Stack overflow."));
+}
+
+#[test]
+fn fmt_keeps_going_past_files_that_do_not_parse() {
+    let broken = script("fmt-broken", "print ;\n");
+    let valid = script("fmt-valid", "print 1 ;\n");
+    let (check_code, check_stdout, _) = rox(&["fmt", "--check", &broken, &valid]);
+    let (code, _, stderr) = rox(&["fmt", &broken, &valid]);
+    let formatted = fs::read_to_string(&valid).unwrap();
+    fs::remove_file(&broken).unwrap();
+    fs::remove_file(&valid).unwrap();
+
+    assert_eq!(check_code, Some(65));
+    assert_eq!(check_stdout, format!("Would reformat {}\n", valid));
+    assert_eq!(code, Some(65));
+    assert!(stderr.contains("Expect expression."), "{}", stderr);
+    assert_eq!(formatted, "print 1;\n");
+}
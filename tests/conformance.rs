@@ -0,0 +1,51 @@
+use std::path::Path;
+
+use rox::conformance;
+use rox::conformance::Expectations;
+
+#[test]
+fn corpus_passes() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox");
+    let results = conformance::run_dir(&corpus).expect("failed to read the test corpus");
+    assert!(!results.is_empty());
+
+    let mut report = String::new();
+    for result in results.iter().filter(|r| !r.passed()) {
+        report.push_str(&format!("FAIL {}\n", result.path.display()));
+        for failure in &result.failures {
+            report.push_str(&format!("  {}\n", failure));
+        }
+    }
+    assert!(report.is_empty(), "\n{}", report);
+}
+
+#[test]
+fn parses_expectation_comments() {
+    let source = "print 1; // expect: 1\n\
+                  var a = ; // Error at ';': Expect expression.\n\
+                  // [line 7] Error at end: Expect '}' after block.\n\
+                  nil(); // expect runtime error: Can only call functions and classes.\n";
+    let expectations = Expectations::parse(source);
+
+    assert_eq!(expectations.output, vec!["1"]);
+    assert_eq!(
+        expectations.compile_errors,
+        vec![
+            "[line 2] Error at ';': Expect expression.",
+            "[line 7] Error at end: Expect '}' after block.",
+        ]
+    );
+    assert_eq!(
+        expectations.runtime_error,
+        Some(("Can only call functions and classes.".to_string(), 4))
+    );
+}
+
+#[test]
+fn reports_mismatches() {
+    let source = "print 1; // expect: 2\n";
+    let outcome = conformance::run_source(source.to_string());
+    let failures = conformance::check(&Expectations::parse(source), &outcome);
+
+    assert_eq!(failures, vec!["Output differs:", "  - 2", "  + 1"]);
+}
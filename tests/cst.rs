@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::Path;
+
+use rox::cst::{self, SyntaxKind, TriviaKind};
+use rox::lexer::TokenType;
+
+#[test]
+fn attaches_trivia_to_tokens() {
+    let tokens = cst::tokenize("// header\nvar a = 1; // note\n\n");
+
+    let var = &tokens[0];
+    assert_eq!(var.kind, TokenType::Var);
+    let leading: Vec<TriviaKind> = var.leading.iter().map(|t| t.kind).collect();
+    assert_eq!(leading, vec![TriviaKind::Comment, TriviaKind::Newline]);
+
+    let semicolon = &tokens[4];
+    assert_eq!(semicolon.kind, TokenType::Semicolon);
+    let trailing: Vec<&str> = semicolon.trailing.iter().map(|t| t.text.as_str()).collect();
+    assert_eq!(trailing, vec![" ", "// note"]);
+
+    let eof = tokens.last().unwrap();
+    assert_eq!(eof.kind, TokenType::Eof);
+    assert_eq!(eof.leading.len(), 2);
+    assert_eq!(eof.line, 4);
+}
+
+#[test]
+fn builds_nodes_for_the_grammar() {
+    let tree = cst::parse("fun add(a, b) { return a + b * 2; }\nprint add(1, 2).field;");
+    let kinds: Vec<SyntaxKind> = tree.child_nodes().map(|n| n.kind).collect();
+    assert_eq!(kinds, vec![SyntaxKind::FunDecl, SyntaxKind::PrintStmt]);
+
+    let function = tree.child_nodes().next().unwrap();
+    let function = function.child_nodes().next().unwrap();
+    let children: Vec<SyntaxKind> = function.child_nodes().map(|n| n.kind).collect();
+    assert_eq!(children, vec![SyntaxKind::Params, SyntaxKind::Block]);
+
+    let print = tree.child_nodes().nth(1).unwrap();
+    let get = print.child_nodes().next().unwrap();
+    assert_eq!(get.kind, SyntaxKind::Get);
+    assert_eq!(get.child_nodes().next().unwrap().kind, SyntaxKind::Call);
+}
+
+#[test]
+fn builds_for_in_loops() {
+    let tree = cst::parse("for (var x in [1, {\"a\": 2}]) print x[0];\nfor (var i = 0; i < 1;) {}");
+    let kinds: Vec<SyntaxKind> = tree.child_nodes().map(|n| n.kind).collect();
+    assert_eq!(kinds, vec![SyntaxKind::ForInStmt, SyntaxKind::ForStmt]);
+
+    let for_in = tree.child_nodes().next().unwrap();
+    let children: Vec<SyntaxKind> = for_in.child_nodes().map(|n| n.kind).collect();
+    assert_eq!(children, vec![SyntaxKind::List, SyntaxKind::PrintStmt]);
+    let list = for_in.child_nodes().next().unwrap();
+    let elements: Vec<SyntaxKind> = list.child_nodes().map(|n| n.kind).collect();
+    assert_eq!(elements, vec![SyntaxKind::Literal, SyntaxKind::Map]);
+}
+
+#[test]
+fn builds_imports() {
+    let tree = cst::parse("import \"a.lox\" as a;\nfrom \"b.lox\" import b, c;\nfrom(1);");
+    let kinds: Vec<SyntaxKind> = tree.child_nodes().map(|n| n.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            SyntaxKind::ImportDecl,
+            SyntaxKind::ImportDecl,
+            SyntaxKind::ExprStmt
+        ]
+    );
+}
+
+#[test]
+fn keeps_invalid_code() {
+    let source = "var = ;\nprint 1 +;\n} @ \"open";
+    let tree = cst::parse(source);
+    assert_eq!(tree.to_string(), source);
+    assert!(tree.tokens().iter().any(|t| t.kind == TokenType::Error));
+}
+
+#[test]
+fn round_trips_the_corpus() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox");
+    let mut dirs = vec![corpus];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let source = fs::read_to_string(&path).unwrap();
+            assert_eq!(
+                cst::parse(&source).to_string(),
+                source,
+                "{}",
+                path.display()
+            );
+        }
+    }
+}
+
+#[test]
+fn tracks_lines_across_multiline_strings() {
+    let source = "var s = \"one\ntwo \\q\";\nprint s;";
+    let tokens = cst::tokenize(source);
+    assert_eq!(cst::parse(source).to_string(), source);
+
+    let error = tokens.iter().find(|t| t.kind == TokenType::Error).unwrap();
+    assert_eq!((error.line, error.column), (2, 5));
+    let print = tokens.iter().find(|t| t.kind == TokenType::Print).unwrap();
+    assert_eq!((print.line, print.column), (3, 1));
+}
@@ -0,0 +1,129 @@
+use std::fs;
+use std::path::Path;
+
+use rox::formatter::{self, FormatOptions};
+
+fn format(source: &str) -> String {
+    formatter::format_source(source, &FormatOptions::default()).unwrap()
+}
+
+#[test]
+fn formats_canonical_layout() {
+    let source = "var a=1;   var b = -a+ -2 ;\n\
+                  fun add(x,y){return x+y;}\n\
+                  if(!a){print \"no\";}else{ print add( a , b ) ;}\n\
+                  for(var i=0;i<3;i=i+1)print i;\n\
+                  class Empty{}\n";
+    let expected = "var a = 1;\n\
+                    var b = -a + -2;\n\
+                    fun add(x, y) {\n  return x + y;\n}\n\
+                    if (!a) {\n  print \"no\";\n} else {\n  print add(a, b);\n}\n\
+                    for (var i = 0; i < 3; i = i + 1) print i;\n\
+                    class Empty {}\n";
+    assert_eq!(format(source), expected);
+}
+
+#[test]
+fn keeps_comments_and_single_blank_lines() {
+    let source = "// header\n\n\n\
+                  var a = 1;   // trailing\n\
+                  {\n\
+                  // inside\n\
+                  print a;\n\
+                  }\n";
+    let expected = "// header\n\n\
+                    var a = 1; // trailing\n\
+                    {\n  // inside\n  print a;\n}\n";
+    assert_eq!(format(source), expected);
+}
+
+#[test]
+fn respects_indent_and_line_width() {
+    let options = FormatOptions {
+        indent_width: 4,
+        line_width: 20,
+    };
+    let source = "fun f() { print combine(first, second); }";
+    let expected = "fun f() {\n    print combine(\n        first,\n        second\n    );\n}\n";
+    assert_eq!(
+        formatter::format_source(source, &options).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn refuses_invalid_source() {
+    let errors = formatter::format_source("print ;", &FormatOptions::default()).unwrap_err();
+    assert_eq!(
+        errors[0].to_string(),
+        "[line 1] Error at ';': Expect expression."
+    );
+}
+
+#[test]
+fn formatting_is_idempotent_over_the_corpus() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox");
+    let mut dirs = vec![corpus];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let source = fs::read_to_string(&path).unwrap();
+            let Ok(formatted) = formatter::format_source(&source, &FormatOptions::default()) else {
+                continue;
+            };
+            assert_eq!(
+                format(&formatted),
+                formatted,
+                "{} is not stable",
+                path.display()
+            );
+        }
+    }
+}
+
+#[test]
+fn keeps_interpolated_strings_tight() {
+    assert_eq!(
+        format("print \"a ${ x+1 } b ${ f( y ) }\";"),
+        "print \"a ${x + 1} b ${f(y)}\";\n"
+    );
+}
+
+#[test]
+fn keeps_list_brackets_tight() {
+    assert_eq!(
+        format("var l = [ 1,2 ] ;print l [ 0 ]+fns[1] ( ) [ 2 ];"),
+        "var l = [1, 2];\nprint l[0] + fns[1]()[2];\n"
+    );
+}
+
+#[test]
+fn keeps_map_literals_on_one_line() {
+    assert_eq!(
+        format("var m = { \"a\" : 1,2:{ } };\n{ print m [\"a\"]; f({ 1:2 }); }"),
+        "var m = {\"a\": 1, 2: {}};\n{\n  print m[\"a\"];\n  f({1: 2});\n}\n"
+    );
+}
+
+#[test]
+fn keeps_thrown_map_literals_on_one_line() {
+    assert_eq!(format("throw { \"a\" : 1 } ;"), "throw {\"a\": 1};\n");
+}
+
+#[test]
+fn keeps_iterated_map_literals_on_one_line() {
+    assert_eq!(
+        format("for (var k in { \"a\" : 1 }) print k;"),
+        "for (var k in {\"a\": 1}) print k;\n"
+    );
+}
+
+#[test]
+fn keeps_block_comments_in_place() {
+    let source = "/* header\n   more */\nvar a = /* inline */ 1;\n/* a */ print a;\nprint a; /* trailing */\n";
+    assert_eq!(format(source), source);
+}
@@ -0,0 +1,279 @@
+//! Deterministic counterparts of the cargo-fuzz targets in `fuzz/`, run with a fixed seed so
+//! they can be part of plain `cargo test`.
+
+use std::thread;
+
+use rox::cst;
+use rox::interpreter::INTERPRETER_STACK_SIZE;
+use rox::lexer::Lexer;
+use rox::parser::{Parser, Stmt};
+use rox::printer;
+use rox::resolver::Resolver;
+
+const CASES: usize = 2000;
+
+// xorshift64*, small and good enough to drive generators reproducibly.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+const CHARACTERS: &[char] = &[
+    '(', ')', '{', '}', '[', ']', ',', ':', '.', '-', '+', ';', '/', '*', '!', '=', '<', '>', '"',
+    ' ', '\t', '\n', '\r', 'a', 'z', '_', '0', '9', '#', '@', '|', '\\', '$', 'é', '字', '\u{0}',
+];
+
+const VOCABULARY: &[&str] = &[
+    "(", ")", "{", "}", "[", "]", ",", ":", ".", "-", "+", ";", "/", "*", "%", "!", "!=", "=",
+    "==", ">", ">=", "<", "<=", "a", "b", "init", "\"s\"", "1", "2.5", "and", "as", "break",
+    "class", "catch", "continue", "else", "false", "finally", "from", "fun", "for", "if", "import",
+    "in", "nil", "or", "print", "return", "super", "this", "throw", "true", "try", "var", "while",
+];
+
+fn random_source(rng: &mut Rng) -> String {
+    let len = rng.below(40);
+    (0..len).map(|_| *rng.pick(CHARACTERS)).collect()
+}
+
+fn random_token_stream(rng: &mut Rng) -> String {
+    let len = rng.below(40);
+    let tokens: Vec<&str> = (0..len).map(|_| *rng.pick(VOCABULARY)).collect();
+    tokens.join(" ")
+}
+
+fn random_expr(rng: &mut Rng, depth: usize) -> String {
+    if depth == 0 {
+        return rng
+            .pick(&[
+                "1",
+                "2.5",
+                "\"s\"",
+                "\"\\t\\$\"",
+                "true",
+                "false",
+                "nil",
+                "a",
+                "b",
+            ])
+            .to_string();
+    }
+    let depth = depth - 1;
+    match rng.below(12) {
+        0 => format!("({})", random_expr(rng, depth)),
+        6 => format!("\"x ${{{}}} y\"", random_expr(rng, depth)),
+        1 => format!("{}{}", rng.pick(&["-", "!"]), random_expr(rng, depth)),
+        2 => format!("{}.field", random_expr(rng, depth)),
+        3 => format!(
+            "f({}, {})",
+            random_expr(rng, depth),
+            random_expr(rng, depth)
+        ),
+        4 => format!("a = {}", random_expr(rng, depth)),
+        7 => format!("[{}, {}]", random_expr(rng, depth), random_expr(rng, depth)),
+        9 => format!(
+            "{{{}: {}}}",
+            random_expr(rng, depth),
+            random_expr(rng, depth)
+        ),
+        8 => format!(
+            "{}[{}] = {}",
+            random_expr(rng, depth),
+            random_expr(rng, depth),
+            random_expr(rng, depth)
+        ),
+        5 => format!(
+            "{}.field = {}",
+            random_expr(rng, depth),
+            random_expr(rng, depth)
+        ),
+        _ => format!(
+            "{} {} {}",
+            random_expr(rng, depth),
+            rng.pick(&[
+                "+", "-", "*", "/", "%", "==", "!=", "<", "<=", ">", ">=", "and", "or"
+            ]),
+            random_expr(rng, depth)
+        ),
+    }
+}
+
+fn random_stmt(rng: &mut Rng, depth: usize) -> String {
+    if depth == 0 {
+        return format!("print {};", random_expr(rng, 2));
+    }
+    let depth = depth - 1;
+    match rng.below(11) {
+        0 => format!("var a = {};", random_expr(rng, 3)),
+        9 => format!(
+            "try {{ {} throw {}; }} catch (e) {{ {} }} finally {{ {} }}",
+            random_stmt(rng, depth),
+            random_expr(rng, 2),
+            random_stmt(rng, depth),
+            random_stmt(rng, depth)
+        ),
+        8 => format!(
+            "for (var a in {}) {{ if (a) break; {} continue; }}",
+            random_expr(rng, 2),
+            random_stmt(rng, depth)
+        ),
+        1 => format!(
+            "{{ {} {} }}",
+            random_stmt(rng, depth),
+            random_stmt(rng, depth)
+        ),
+        2 => format!(
+            "if ({}) {} else {}",
+            random_expr(rng, 2),
+            random_stmt(rng, depth),
+            random_stmt(rng, depth)
+        ),
+        3 => format!("if ({}) {}", random_expr(rng, 2), random_stmt(rng, depth)),
+        4 => format!(
+            "while ({}) {}",
+            random_expr(rng, 2),
+            random_stmt(rng, depth)
+        ),
+        5 => format!(
+            "for (var i = 0; i < {}; i = i + 1) {}",
+            random_expr(rng, 1),
+            random_stmt(rng, depth)
+        ),
+        6 => format!(
+            "fun f(x, y) {{ {} return {}; }}",
+            random_stmt(rng, depth),
+            random_expr(rng, 2)
+        ),
+        7 => format!(
+            "class C < B {{ init(x) {{ this.x = x; }} m() {{ {} return super.m; }} }}",
+            random_stmt(rng, depth)
+        ),
+        _ => format!("{};", random_expr(rng, 3)),
+    }
+}
+
+fn parse(source: &str) -> Option<Vec<Stmt>> {
+    let mut lexer = Lexer::from_source(source);
+    Parser::new(&mut lexer).parse().ok()
+}
+
+fn assert_round_trips(source: &str) {
+    let Some(statements) = parse(source) else {
+        return;
+    };
+    let printed = printer::print_program(&statements);
+    let reparsed =
+        parse(&printed).unwrap_or_else(|| panic!("printed source does not parse:\n{}", printed));
+    assert_eq!(
+        printed,
+        printer::print_program(&reparsed),
+        "printing is not idempotent for:\n{}",
+        source
+    );
+}
+
+// The parser is recursive, give it the same stack the interpreter gets in `rox run`.
+fn with_big_stack(test: fn()) {
+    thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(test)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn lexer_never_panics() {
+    with_big_stack(|| {
+        let mut rng = Rng(0x5eed_0001);
+        for _ in 0..CASES {
+            let source = random_source(&mut rng);
+            for _token in Lexer::from_source(&source) {}
+        }
+        for ending in ["=", "<", ">", "!", "/", "\"", "1.", "a"] {
+            for _token in Lexer::from_source(&format!("print 1 {}", ending)) {}
+        }
+    });
+}
+
+#[test]
+fn parser_never_panics() {
+    with_big_stack(|| {
+        let mut rng = Rng(0x5eed_0002);
+        for _ in 0..CASES {
+            let source = if rng.below(2) == 0 {
+                random_token_stream(&mut rng)
+            } else {
+                random_source(&mut rng)
+            };
+            if let Some(statements) = parse(&source) {
+                let _ = Resolver::new().resolve(&statements);
+            }
+        }
+    });
+}
+
+#[test]
+fn deep_nesting_is_an_error() {
+    with_big_stack(|| {
+        for source in [
+            format!("print {}1{};", "(".repeat(10_000), ")".repeat(10_000)),
+            format!("print {}1;", "!".repeat(10_000)),
+            format!("{}{}", "{".repeat(10_000), "}".repeat(10_000)),
+            format!("{}print 1;", "if (true) ".repeat(10_000)),
+            format!("if (a) print 0;{}", " else if (a) print 1;".repeat(10_000)),
+        ] {
+            assert!(parse(&source).is_none());
+        }
+        // The branches of an `else if` chain aren't nested in each other.
+        let chain = format!(
+            "if (a) print 0;{} else print 2;",
+            " else if (a) print 1;".repeat(500)
+        );
+        assert!(parse(&chain).is_some());
+    });
+}
+
+#[test]
+fn printer_round_trips() {
+    with_big_stack(|| {
+        let mut rng = Rng(0x5eed_0003);
+        for _ in 0..CASES {
+            let statements: Vec<String> = (0..1 + rng.below(4))
+                .map(|_| random_stmt(&mut rng, 3))
+                .collect();
+            assert_round_trips(&statements.join("\n"));
+            assert_round_trips(&random_token_stream(&mut rng));
+        }
+    });
+}
+
+#[test]
+fn cst_round_trips() {
+    with_big_stack(|| {
+        let mut rng = Rng(0x5eed_0004);
+        for _ in 0..CASES {
+            let source = match rng.below(3) {
+                0 => random_source(&mut rng),
+                1 => random_token_stream(&mut rng),
+                _ => random_stmt(&mut rng, 3),
+            };
+            assert_eq!(cst::parse(&source).to_string(), source);
+        }
+        let deep = format!("print {}1;", "(-".repeat(10_000));
+        assert_eq!(cst::parse(&deep).to_string(), deep);
+    });
+}
@@ -0,0 +1,96 @@
+use rox::highlighter::{self, Highlight};
+
+fn highlights(source: &str) -> Vec<(Highlight, String)> {
+    highlighter::highlight(source)
+        .into_iter()
+        .filter_map(|(highlight, text)| highlight.map(|h| (h, text)))
+        .collect()
+}
+
+fn of(spans: &[(Highlight, String)], text: &str) -> Vec<Highlight> {
+    spans
+        .iter()
+        .filter(|(_, t)| t == text)
+        .map(|(h, _)| *h)
+        .collect()
+}
+
+#[test]
+fn classifies_names_by_declaration() {
+    let spans = highlights(
+        "class Point < Base {\n  init(x) { this.x = x; this.show(); }\n  show() { print clock(); }\n}\n\
+         var p = Point(1); // make one\nfun twice(f) { f(); f(); }\n",
+    );
+
+    assert_eq!(of(&spans, "Point"), vec![Highlight::Class; 2]);
+    assert_eq!(of(&spans, "Base"), vec![Highlight::Class]);
+    assert_eq!(
+        of(&spans, "x"),
+        vec![
+            Highlight::Parameter,
+            Highlight::Property,
+            Highlight::Parameter
+        ]
+    );
+    assert_eq!(of(&spans, "show"), vec![Highlight::Method; 2]);
+    assert_eq!(of(&spans, "clock"), vec![Highlight::Function]);
+    assert_eq!(of(&spans, "p"), vec![Highlight::Variable]);
+    // A parameter that happens to be called stays a parameter.
+    assert_eq!(of(&spans, "f"), vec![Highlight::Parameter; 3]);
+    assert_eq!(of(&spans, "// make one"), vec![Highlight::Comment]);
+    assert_eq!(of(&spans, "class"), vec![Highlight::Keyword]);
+    assert_eq!(of(&spans, "="), vec![Highlight::Operator; 2]);
+    assert_eq!(of(&spans, "1"), vec![Highlight::Number]);
+}
+
+#[test]
+fn highlights_code_that_does_not_parse() {
+    let source = "fun broken(a { print a.b \"open";
+    let spans = highlighter::highlight(source);
+    let text: String = spans.iter().map(|(_, t)| t.as_str()).collect();
+    assert_eq!(text, source);
+
+    let spans = highlights(source);
+    assert_eq!(of(&spans, "broken"), vec![Highlight::Function]);
+    assert_eq!(of(&spans, "b"), vec![Highlight::Property]);
+    assert_eq!(of(&spans, "\"open"), vec![Highlight::Error]);
+}
+
+#[test]
+fn renders_ansi() {
+    let out = highlighter::to_ansi("print \"hi\"; // note");
+    assert_eq!(
+        out,
+        "\x1b[35mprint\x1b[0m \x1b[32m\"hi\"\x1b[0m; \x1b[90m// note\x1b[0m"
+    );
+}
+
+#[test]
+fn renders_standalone_html() {
+    let out = highlighter::to_html("print 1 < 2 and \"<&>\";", "a<b>.lox");
+    assert!(out.starts_with("<!DOCTYPE html>"));
+    assert!(out.contains("<title>a&lt;b&gt;.lox</title>"));
+    assert!(out.contains("<style>"));
+    assert!(out.contains(
+        "<span class=\"operator\">&lt;</span> <span class=\"number\">2</span> \
+         <span class=\"keyword\">and</span> <span class=\"string\">&quot;&lt;&amp;&gt;&quot;</span>"
+    ));
+    assert!(out.ends_with("</html>\n"));
+}
+
+#[test]
+fn highlights_import_words_only_in_imports() {
+    let spans = highlights(
+        "import \"a.lox\" as a;\nfrom \"b.lox\" import b;\nvar from = 1;\nvar as = from;\n",
+    );
+
+    assert_eq!(
+        of(&spans, "from"),
+        vec![Highlight::Keyword, Highlight::Variable, Highlight::Variable]
+    );
+    assert_eq!(
+        of(&spans, "as"),
+        vec![Highlight::Keyword, Highlight::Variable]
+    );
+    assert_eq!(of(&spans, "import"), vec![Highlight::Keyword; 2]);
+}
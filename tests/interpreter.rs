@@ -0,0 +1,430 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::rc::Rc;
+use std::time::Duration;
+
+use rox::CapturedOutput;
+use rox::Interpreter;
+use rox::NativeFunction;
+use rox::RoxError;
+use rox::Value;
+use rox::interpreter::Limits;
+use rox::interpreter::Permissions;
+use rox::interpreter::RuntimeErrorKind;
+use rox::interpreter::StackFrame;
+
+fn runtime_error(source: &str) -> String {
+    match Interpreter::new().eval(source) {
+        Err(error @ RoxError::RuntimeError(_)) => error.to_string(),
+        other => panic!(
+            "expected a runtime error, got {:?}",
+            other.map(|v| v.to_string())
+        ),
+    }
+}
+
+#[test]
+fn uncaught_throw_prints_stack_trace() {
+    let source = "fun inner() {\n  throw \"boom\";\n}\nfun outer() {\n  inner();\n}\nouter();\n";
+    assert_eq!(
+        runtime_error(source),
+        "boom\n[line 2] in inner()\n[line 5] in outer()\n[line 7] in script"
+    );
+}
+
+#[test]
+fn rethrown_error_keeps_original_trace() {
+    let source =
+        "fun fail() {\n  throw \"boom\";\n}\ntry {\n  fail();\n} catch (e) {\n  throw e;\n}\n";
+    assert_eq!(
+        runtime_error(source),
+        "boom\n[line 2] in fail()\n[line 5] in script"
+    );
+}
+
+fn frame(function: Option<&str>, line: usize) -> StackFrame {
+    StackFrame {
+        function: function.map(str::to_string),
+        line,
+    }
+}
+
+#[test]
+fn runtime_errors_record_the_call_stack() {
+    let source = "fun greet(name) {\n  return \"Hi \" + name.first;\n}\n\ngreet(42);\n";
+    let Err(RoxError::RuntimeError(error)) = Interpreter::new().eval(source) else {
+        panic!("expected a runtime error");
+    };
+    assert_eq!(error.line, 2);
+    assert_eq!(error.trace, vec![frame(Some("greet"), 2), frame(None, 5)]);
+    assert_eq!(
+        error.to_string(),
+        "Only instances have properties.\n[line 2] in greet()\n[line 5] in script"
+    );
+}
+
+#[test]
+fn default_limits_fit_a_default_thread_stack() {
+    // Spawned threads get 2 MiB of stack, which the default call depth alone could exhaust.
+    let error = std::thread::spawn(|| {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_limits(Limits {
+            max_call_depth: usize::MAX,
+            ..Limits::default()
+        });
+        match interpreter.eval("fun recurse() { recurse(); }\nrecurse();") {
+            Err(RoxError::RuntimeError(error)) => error,
+            other => panic!("expected a runtime error, got {:?}", other),
+        }
+    })
+    .join()
+    .unwrap();
+    assert_eq!(error.kind, RuntimeErrorKind::StackOverflow);
+    assert_eq!(error.message, "Stack overflow.");
+}
+
+fn limited(limits: Limits) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_limits(limits);
+    interpreter
+}
+
+fn error_kind(result: Result<Value, RoxError>) -> RuntimeErrorKind {
+    match result {
+        Err(RoxError::RuntimeError(error)) => error.kind,
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn step_limit_stops_runaway_loops() {
+    let mut interpreter = limited(Limits {
+        max_steps: Some(1000),
+        ..Limits::default()
+    });
+    let result = interpreter.eval("while (true) {}");
+    assert_eq!(error_kind(result), RuntimeErrorKind::StepLimit);
+}
+
+#[test]
+fn step_limit_applies_to_each_eval_and_call() {
+    let mut interpreter = limited(Limits {
+        max_steps: Some(1000),
+        ..Limits::default()
+    });
+    interpreter
+        .eval("fun count() { for (var i = 0; i < 100; i = i + 1) {} }")
+        .unwrap();
+    for _ in 0..20 {
+        interpreter.eval("count();").unwrap();
+        interpreter.call("count", vec![]).unwrap();
+    }
+}
+
+#[test]
+fn step_limit_stops_endless_for_in_loops() {
+    let mut interpreter = limited(Limits {
+        max_steps: Some(1000),
+        ..Limits::default()
+    });
+    let result = interpreter.eval("for (var x in range(0, 1/0)) {}");
+    assert_eq!(error_kind(result), RuntimeErrorKind::StepLimit);
+}
+
+#[test]
+fn interrupt_stops_a_running_script() {
+    let mut interpreter = Interpreter::new();
+    let handle = interpreter.interrupt_handle();
+    let interrupter = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        handle.interrupt();
+    });
+    let result = interpreter.eval("while (true) {}");
+    interrupter.join().unwrap();
+    assert_eq!(error_kind(result), RuntimeErrorKind::Interrupted);
+    assert_eq!(interpreter.eval("1 + 2;").unwrap(), Value::Number(3.0));
+}
+
+#[test]
+fn interrupt_before_a_run_stops_it() {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval("fun spin() { while (true) {} }").unwrap();
+    interpreter.interrupt_handle().interrupt();
+    let result = interpreter.eval("spin();");
+    assert_eq!(error_kind(result), RuntimeErrorKind::Interrupted);
+    interpreter.interrupt_handle().interrupt();
+    let result = interpreter.call("spin", vec![]);
+    assert_eq!(error_kind(result), RuntimeErrorKind::Interrupted);
+}
+
+#[test]
+fn interrupt_ends_with_the_run_it_stopped() {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval("fun answer() { return 42; }").unwrap();
+    // A run that finishes without noticing the interrupt still uses it up.
+    interpreter.interrupt_handle().interrupt();
+    interpreter.eval("1;").unwrap();
+    assert_eq!(interpreter.eval("answer();").unwrap(), Value::Number(42.0));
+}
+
+#[test]
+fn finally_cannot_swallow_a_limit() {
+    let mut interpreter = limited(Limits {
+        max_steps: Some(1000),
+        ..Limits::default()
+    });
+    let result = interpreter
+        .eval("fun f() { try { while (true) {} } catch (e) {} finally { return; } }\nf();");
+    assert_eq!(error_kind(result), RuntimeErrorKind::StepLimit);
+}
+
+#[test]
+fn interrupt_also_stops_finally_blocks() {
+    let mut interpreter = Interpreter::new();
+    interpreter.interrupt_handle().interrupt();
+    let result = interpreter.eval("try { while (true) {} } finally { while (true) {} }");
+    assert_eq!(error_kind(result), RuntimeErrorKind::Interrupted);
+}
+
+#[test]
+fn allocation_limit_stops_runaway_allocation() {
+    let mut interpreter = limited(Limits {
+        max_allocated_bytes: Some(10_000),
+        ..Limits::default()
+    });
+    let result = interpreter.eval("var s = \"a\"; while (true) s = s + s;");
+    assert_eq!(error_kind(result), RuntimeErrorKind::AllocationLimit);
+}
+
+#[test]
+fn allocation_limit_applies_to_each_eval_and_call() {
+    let mut interpreter = limited(Limits {
+        max_allocated_bytes: Some(10_000),
+        ..Limits::default()
+    });
+    interpreter
+        .eval("class Point {} fun make() { var p = Point(); p.name = \"point\"; }")
+        .unwrap();
+    for _ in 0..1000 {
+        interpreter.eval("make();").unwrap();
+        interpreter.call("make", vec![]).unwrap();
+    }
+}
+
+#[test]
+fn stack_overflow_trace_collapses_repeated_frames() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_limits(Limits {
+        max_call_depth: 10,
+        ..Limits::default()
+    });
+    let source = "fun recurse() {\n  recurse();\n}\nrecurse();\n";
+    let Err(error) = interpreter.eval(source) else {
+        panic!("expected a runtime error");
+    };
+    assert_eq!(
+        error.to_string(),
+        "Stack overflow.\n[line 2] in recurse()\n[previous frame repeated 9 more times]\n[line 4] in script"
+    );
+}
+
+#[test]
+fn host_calls_leave_out_the_script_frame() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval("fun fail(x) {\n  return -x;\n}\n")
+        .unwrap();
+    let Err(RoxError::RuntimeError(error)) = interpreter.call("fail", vec![Value::from("a")])
+    else {
+        panic!("expected a runtime error");
+    };
+    assert_eq!(error.trace, vec![frame(Some("fail"), 2)]);
+}
+
+#[test]
+fn natives_can_read_the_call_stack() {
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::new();
+    let recorded = Rc::clone(&seen);
+    interpreter.define_native(NativeFunction::new("where", 0, move |interpreter, _| {
+        *recorded.borrow_mut() = interpreter.call_stack();
+        Ok(Value::Nil)
+    }));
+    interpreter
+        .eval("fun outer() {\n  inner();\n}\nfun inner() {\n  where();\n}\nouter();\n")
+        .unwrap();
+    assert_eq!(
+        *seen.borrow(),
+        vec![
+            frame(Some("inner"), 5),
+            frame(Some("outer"), 2),
+            frame(None, 7)
+        ]
+    );
+}
+
+fn allowed(fs: bool, env: bool) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_permissions(Permissions { fs, env });
+    interpreter
+}
+
+#[test]
+fn reads_and_writes_files_when_allowed() {
+    let dir = env::temp_dir().join(format!("rox-fs-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut interpreter = allowed(true, false);
+    interpreter.set_global("dir", dir.to_string_lossy().into_owned());
+
+    let result = interpreter.eval(
+        "writeFile(dir + \"/b.txt\", \"bee\");\nwriteFile(dir + \"/a.txt\", \"ay\");\n\
+         readFile(dir + \"/a.txt\") + str(listDir(dir));",
+    );
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(result.unwrap().to_string(), "ay[\"a.txt\", \"b.txt\"]");
+
+    let Err(error) = interpreter.eval("readFile(dir + \"/a.txt\");") else {
+        panic!("expected a missing file to be an error");
+    };
+    assert!(error.to_string().starts_with("Could not read file"));
+}
+
+#[test]
+fn reads_environment_and_args_when_allowed() {
+    let mut interpreter = allowed(false, true);
+    interpreter.set_args(vec!["one".to_string(), "two".to_string()]);
+    assert_eq!(
+        interpreter.eval("args();").unwrap().to_string(),
+        "[\"one\", \"two\"]"
+    );
+    assert_eq!(
+        interpreter
+            .eval("getenv(\"CARGO_MANIFEST_DIR\");")
+            .unwrap()
+            .to_string(),
+        env!("CARGO_MANIFEST_DIR")
+    );
+    assert_eq!(
+        interpreter
+            .eval("getenv(\"ROX_SURELY_UNSET_VARIABLE\");")
+            .unwrap(),
+        Value::Nil
+    );
+    // Environment access doesn't grant file access.
+    assert!(interpreter.eval("listDir(\".\");").is_err());
+}
+
+#[test]
+fn imports_outside_the_script_directory_need_file_access() {
+    let dir = env::temp_dir().join(format!("rox-import-test-{}", std::process::id()));
+    fs::create_dir_all(dir.join("scripts")).unwrap();
+    fs::write(dir.join("secret.lox"), "var secret = 42;").unwrap();
+    let secret = dir.join("secret.lox").to_string_lossy().into_owned();
+    let script = dir.join("scripts/main.lox");
+
+    let mut denied = Interpreter::new();
+    denied.set_script_path(&script);
+    let absolute = denied.eval(&format!("import \"{}\" as s;", secret));
+    let relative = denied.eval("import \"../secret.lox\" as s;");
+    // Without a script path there is no directory to import from at all.
+    let no_script = Interpreter::new().eval(&format!("import \"{}\" as s;", secret));
+
+    let mut allowed = allowed(true, false);
+    allowed.set_script_path(&script);
+    let imported = allowed.eval(&format!("import \"{}\" as s;\ns.secret;", secret));
+    fs::remove_dir_all(&dir).unwrap();
+
+    for result in [absolute, relative, no_script] {
+        let Err(RoxError::RuntimeError(error)) = result else {
+            panic!("expected the import to be denied");
+        };
+        assert!(
+            error
+                .message
+                .contains("from outside the script's directory"),
+            "{}",
+            error.message
+        );
+    }
+    assert_eq!(imported.unwrap(), Value::Number(42.0));
+}
+
+#[test]
+fn eval_keeps_state_between_calls() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval("var total = 0;\nfun add(n) { var before = total; total = before + n; }")
+        .unwrap();
+    for n in 1..=100 {
+        interpreter.eval(&format!("add({});", n)).unwrap();
+    }
+    assert_eq!(interpreter.eval("total;").unwrap(), Value::Number(5050.0));
+    // Statements that end without an expression give nil.
+    assert_eq!(interpreter.eval("var unused = 1;").unwrap(), Value::Nil);
+}
+
+#[test]
+fn call_runs_global_functions_from_the_host() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval("fun greet(name) { return \"Hello, \" + name + \"!\"; }")
+        .unwrap();
+    let greeting = interpreter.call("greet", vec![Value::from("Ann")]).unwrap();
+    assert_eq!(String::try_from(greeting).unwrap(), "Hello, Ann!");
+
+    let Err(RoxError::RuntimeError(error)) = interpreter.call("missing", vec![]) else {
+        panic!("expected a runtime error");
+    };
+    assert_eq!(error.message, "Undefined variable 'missing'.");
+    let Err(RoxError::RuntimeError(error)) = interpreter.call("greet", vec![]) else {
+        panic!("expected a runtime error");
+    };
+    assert_eq!(error.message, "Expected 1 arguments but got 0.");
+}
+
+#[test]
+fn globals_can_be_read_and_written_by_the_host() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_global("limit", 10);
+    interpreter.set_global("name", "Ann");
+    interpreter.set_global("missing", None::<f64>);
+    assert_eq!(interpreter.eval("limit * 2;").unwrap(), Value::Number(20.0));
+    interpreter.eval("var doubled = name + name;").unwrap();
+    assert_eq!(
+        interpreter.get_global("doubled"),
+        Some(Value::from("AnnAnn"))
+    );
+    assert_eq!(interpreter.get_global("missing"), Some(Value::Nil));
+    assert_eq!(interpreter.get_global("undefined"), None);
+}
+
+#[test]
+fn values_convert_to_rust_types() {
+    let mut interpreter = Interpreter::new();
+    assert!(bool::try_from(interpreter.eval("1 < 2;").unwrap()).unwrap());
+    assert_eq!(f64::try_from(interpreter.eval("1 + 2;").unwrap()), Ok(3.0));
+    assert_eq!(
+        String::try_from(interpreter.eval("\"a\" + \"b\";").unwrap()),
+        Ok("ab".to_string())
+    );
+    assert_eq!(
+        Vec::<Value>::try_from(interpreter.eval("[1, true];").unwrap()),
+        Ok(vec![Value::Number(1.0), Value::Bool(true)])
+    );
+
+    let mismatch = f64::try_from(Value::from("one")).unwrap_err();
+    assert_eq!(mismatch.to_string(), "Expected a number but got a string.");
+}
+
+#[test]
+fn captured_output_collects_prints() {
+    let output = CapturedOutput::new();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(output.clone());
+    interpreter.eval("print 1;\nprint \"two\";").unwrap();
+    assert_eq!(output.take_string(), "1\ntwo\n");
+    // Taking the output clears it.
+    interpreter.eval("print nil;").unwrap();
+    assert_eq!(output.take_string(), "nil\n");
+}
@@ -0,0 +1,106 @@
+use rox::linter::{self, LintOptions, Rule, Severity};
+
+// The rule and line of everything the default rules find in the source.
+fn findings(source: &str) -> Vec<(Rule, usize)> {
+    linter::lint_source(source, &LintOptions::default())
+        .unwrap()
+        .iter()
+        .map(|d| (d.rule, d.line))
+        .collect()
+}
+
+#[test]
+fn reports_each_rule() {
+    let source = "var g = 1;\n\
+                  fun f(a, b, _c) {\n\
+                  \x20 var unused = 1;\n\
+                  \x20 if (a == (a)) print \"same\";\n\
+                  \x20 { var g = 2; print g; }\n\
+                  \x20 while (a) { break; a = 1; }\n\
+                  \x20 return a;\n\
+                  \x20 print \"dead\";\n\
+                  }\n\
+                  if (1 < 2) print f(1, 2, 3);\n\
+                  missing = 3;\n";
+    assert_eq!(
+        findings(source),
+        vec![
+            (Rule::UnusedVariable, 2),
+            (Rule::UnusedVariable, 3),
+            (Rule::SelfComparison, 4),
+            (Rule::ShadowedVariable, 5),
+            (Rule::UnreachableCode, 6),
+            (Rule::UnreachableCode, 8),
+            (Rule::ConstantCondition, 10),
+            (Rule::UndeclaredGlobal, 11),
+        ]
+    );
+}
+
+#[test]
+fn accepts_idiomatic_code() {
+    let source = "var count = 0;\n\
+                  fun counter() {\n\
+                  \x20 var n = 0;\n\
+                  \x20 fun next() { n = n + 1; count = count + 1; return n; }\n\
+                  \x20 return next;\n\
+                  }\n\
+                  for (;;) { if (counter()() > 1) return_later(); }\n\
+                  while (true) {}\n\
+                  fun return_later() { if (count > 0) { return 1; } else { return 2; } }\n\
+                  class A { init(x) { this.x = x; } same() { return this.x == this.y; } }\n\
+                  clock = nil;\n";
+    assert_eq!(findings(source), vec![]);
+}
+
+#[test]
+fn allow_comments_silence_rules() {
+    let source = "fun f(a) { // rox-allow: unused-variable\n\
+                  \x20 // rox-allow: L005, constant-condition\n\
+                  \x20 if (nil) print a != a;\n\
+                  \x20 print a != a;\n\
+                  }\n\
+                  f(1);\n";
+    assert_eq!(findings(source), vec![(Rule::SelfComparison, 4)]);
+}
+
+#[test]
+fn severities_are_configurable() {
+    let source = "{ var a = 1; }\nundeclared = 1;\n";
+    let mut options = LintOptions::default();
+    options.set(Rule::UndeclaredGlobal, Severity::Allow);
+    options.set(Rule::from_name("L001").unwrap(), Severity::Error);
+
+    let diagnostics = linter::lint_source(source, &options).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "[line 1] error L001 (unused-variable): Local variable 'a' is never used."
+    );
+    assert_eq!(
+        Rule::from_name("shadowed-variable"),
+        Some(Rule::ShadowedVariable)
+    );
+    assert_eq!(Rule::from_name("nope"), None);
+}
+
+#[test]
+fn names_unused_catch_variables() {
+    let source = "try { print 1; } catch (e) { print 2; }\n";
+    let diagnostics = linter::lint_source(source, &LintOptions::default()).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "[line 1] warning L001 (unused-variable): Catch variable 'e' is never used."
+    );
+}
+
+#[test]
+fn refuses_code_that_does_not_compile() {
+    let errors =
+        linter::lint_source("fun f() { var a = a; }", &LintOptions::default()).unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "Can't read local variable in its own initializer."
+    );
+}
@@ -0,0 +1,297 @@
+use rox::json::Json;
+use rox::lexer::Lexer;
+use rox::lsp;
+use rox::parser::Parser;
+use rox::symbols::{DeclarationKind, SymbolIndex};
+
+const URI: &str = "file:///test.lox";
+
+const SOURCE: &str = "var a = 1;\n\
+                      fun add(x, y) {\n\
+                      \x20 return x + y + a;\n\
+                      }\n\
+                      class Foo {\n\
+                      \x20 init() { this.go(); }\n\
+                      \x20 go() {}\n\
+                      }\n\
+                      print add(a, 2);\n";
+
+fn frame(message: Json) -> String {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+fn request(id: usize, method: &str, params: Json) -> String {
+    frame(Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("id", id.into()),
+        ("method", method.into()),
+        ("params", params),
+    ]))
+}
+
+fn notification(method: &str, params: Json) -> String {
+    frame(Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("method", method.into()),
+        ("params", params),
+    ]))
+}
+
+fn document() -> Json {
+    Json::object([("uri", URI.into())])
+}
+
+fn at(line: usize, character: usize) -> Json {
+    Json::object([
+        ("textDocument", document()),
+        (
+            "position",
+            Json::object([("line", line.into()), ("character", character.into())]),
+        ),
+    ])
+}
+
+fn open(text: &str) -> String {
+    notification(
+        "textDocument/didOpen",
+        Json::object([(
+            "textDocument",
+            Json::object([("uri", URI.into()), ("text", text.into())]),
+        )]),
+    )
+}
+
+// Feeds the messages to a server and returns the exit code and everything it sent back.
+fn session(messages: &[String]) -> (i32, Vec<Json>) {
+    let input = messages.concat();
+    let mut output = Vec::new();
+    let code = lsp::run(input.as_bytes(), &mut output).unwrap();
+
+    let output = String::from_utf8(output).unwrap();
+    let mut replies = Vec::new();
+    let mut rest = output.as_str();
+    while let Some((header, body)) = rest.split_once("\r\n\r\n") {
+        let length: usize = header
+            .strip_prefix("Content-Length: ")
+            .unwrap()
+            .parse()
+            .unwrap();
+        replies.push(Json::parse(&body[..length]).unwrap());
+        rest = &body[length..];
+    }
+    (code, replies)
+}
+
+fn result(replies: &[Json], id: usize) -> &Json {
+    replies
+        .iter()
+        .find(|r| r.get("id").and_then(Json::as_f64) == Some(id as f64))
+        .and_then(|r| r.get("result"))
+        .unwrap()
+}
+
+fn start(location: &Json) -> (f64, f64) {
+    let start = location.get("range").unwrap().get("start").unwrap();
+    (
+        start.get("line").unwrap().as_f64().unwrap(),
+        start.get("character").unwrap().as_f64().unwrap(),
+    )
+}
+
+#[test]
+fn publishes_diagnostics_on_change() {
+    let change = notification(
+        "textDocument/didChange",
+        Json::object([
+            ("textDocument", document()),
+            (
+                "contentChanges",
+                vec![Json::object([("text", "var a = 1;\nprint ;".into())])].into(),
+            ),
+        ]),
+    );
+    let (_, replies) = session(&[open(SOURCE), change]);
+
+    let diagnostics: Vec<&[Json]> = replies
+        .iter()
+        .map(|r| {
+            r.get("params")
+                .unwrap()
+                .get("diagnostics")
+                .unwrap()
+                .as_array()
+                .unwrap()
+        })
+        .collect();
+    assert!(diagnostics[0].is_empty());
+    assert_eq!(diagnostics[1].len(), 1);
+    assert_eq!(
+        diagnostics[1][0].get("message").unwrap().as_str(),
+        Some("Expect expression.")
+    );
+    assert_eq!(start(&diagnostics[1][0]), (1.0, 6.0));
+}
+
+#[test]
+fn finds_definitions_and_references() {
+    let references_params = Json::object([
+        ("textDocument", document()),
+        (
+            "position",
+            Json::object([("line", 0usize.into()), ("character", 4usize.into())]),
+        ),
+        (
+            "context",
+            Json::object([("includeDeclaration", true.into())]),
+        ),
+    ]);
+    let (_, replies) = session(&[
+        open(SOURCE),
+        request(1, "textDocument/definition", at(8, 7)),
+        request(2, "textDocument/references", references_params),
+        request(3, "textDocument/definition", at(5, 16)),
+    ]);
+
+    assert_eq!(start(result(&replies, 1)), (1.0, 4.0));
+    let references: Vec<(f64, f64)> = result(&replies, 2)
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(start)
+        .collect();
+    assert_eq!(references, vec![(0.0, 4.0), (2.0, 17.0), (8.0, 10.0)]);
+    assert_eq!(start(result(&replies, 3)), (6.0, 2.0));
+}
+
+#[test]
+fn hover_shows_the_declaration_kind() {
+    let (_, replies) = session(&[
+        open(SOURCE),
+        request(1, "textDocument/hover", at(2, 9)),
+        request(2, "textDocument/hover", at(8, 6)),
+        request(3, "textDocument/hover", at(3, 0)),
+    ]);
+
+    let hover = |id| {
+        result(&replies, id)
+            .get("contents")
+            .unwrap()
+            .get("value")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(
+        hover(1),
+        "```lox\n(parameter) x\n```\nparameter declared on line 2"
+    );
+    assert_eq!(
+        hover(2),
+        "```lox\nfun add(x, y)\n```\nglobal function declared on line 2"
+    );
+    assert_eq!(result(&replies, 3), &Json::Null);
+}
+
+#[test]
+fn lists_document_symbols() {
+    let params = Json::object([("textDocument", document())]);
+    let (_, replies) = session(&[
+        open(SOURCE),
+        request(1, "textDocument/documentSymbol", params),
+    ]);
+
+    let symbols = result(&replies, 1).as_array().unwrap();
+    let names: Vec<&str> = symbols
+        .iter()
+        .map(|s| s.get("name").unwrap().as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["add", "Foo"]);
+    let methods: Vec<&str> = symbols[1]
+        .get("children")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s.get("name").unwrap().as_str().unwrap())
+        .collect();
+    assert_eq!(methods, vec!["init", "go"]);
+}
+
+#[test]
+fn follows_the_shutdown_protocol() {
+    let shutdown = frame(Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("id", 1usize.into()),
+        ("method", "shutdown".into()),
+    ]));
+    let exit = frame(Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("method", "exit".into()),
+    ]));
+    let unknown = request(2, "textDocument/unknown", Json::Null);
+
+    let (code, replies) = session(&[unknown, shutdown, exit.clone()]);
+    assert_eq!(code, 0);
+    let error = replies[0].get("error").unwrap();
+    assert_eq!(error.get("code").unwrap().as_f64(), Some(-32601.0));
+
+    assert_eq!(session(&[exit]).0, 1);
+}
+
+#[test]
+fn answers_bad_frames_and_keeps_going() {
+    let no_length = "Content-Type: application/json\r\n\r\n".to_string();
+    let bad_json = "Content-Length: 1\r\n\r\n{".to_string();
+    let shutdown = request(1, "shutdown", Json::Null);
+    let exit = notification("exit", Json::Null);
+
+    let (code, replies) = session(&[no_length, bad_json, shutdown, exit]);
+    assert_eq!(code, 0);
+    assert_eq!(replies.len(), 3);
+    for reply in &replies[..2] {
+        let error = reply.get("error").unwrap();
+        assert_eq!(error.get("code").unwrap().as_f64(), Some(-32700.0));
+    }
+    assert_eq!(result(&replies, 1), &Json::Null);
+}
+
+#[test]
+fn links_names_to_declarations() {
+    let source = "var a = 1;\n{ var a = 2; print a; }\nfun f(a) { return a; }\nprint a;";
+    let mut lexer = Lexer::from_source(source);
+    let statements = Parser::new(&mut lexer).parse().unwrap();
+    let index = SymbolIndex::build(&statements);
+
+    let kinds: Vec<DeclarationKind> = index.declarations.iter().map(|d| d.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            DeclarationKind::Variable,
+            DeclarationKind::Variable,
+            DeclarationKind::Function,
+            DeclarationKind::Parameter,
+        ]
+    );
+    let lines = |declaration| -> Vec<usize> {
+        index
+            .references_to(declaration)
+            .map(|token| token.line)
+            .collect()
+    };
+    assert_eq!(lines(0), vec![4]);
+    assert_eq!(lines(1), vec![2]);
+    assert_eq!(lines(3), vec![3]);
+    assert_eq!(index.declaration_at(4, 7), Some(0));
+}
+
+#[test]
+fn json_round_trips() {
+    let text = r#"{"a":[1,-2.5,true,null],"b":"q\"\\\né😀","c":{}}"#;
+    let value = Json::parse(text).unwrap();
+    assert_eq!(value.get("b").and_then(Json::as_str), Some("q\"\\\né😀"));
+    assert_eq!(Json::parse(&value.to_string()).unwrap(), value);
+    assert!(Json::parse("{\"a\":}").is_err());
+    assert!(Json::parse("[1] 2").is_err());
+}
@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rox::Interpreter;
+use rox::Value;
+use rox::conformance;
+use rox::lexer::Lexer;
+use rox::optimizer;
+use rox::parser::Parser;
+use rox::printer;
+
+fn optimized(source: &str) -> String {
+    let mut lexer = Lexer::from_source(source);
+    let statements = Parser::new(&mut lexer).parse().unwrap();
+    printer::print_program(&optimizer::optimize(&statements))
+}
+
+fn lox_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            lox_files(&path, files);
+        } else if path.extension().is_some_and(|ext| ext == "lox") {
+            files.push(path);
+        }
+    }
+}
+
+#[test]
+fn corpus_behaves_the_same_when_optimized() {
+    let mut files = Vec::new();
+    lox_files(
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox"),
+        &mut files,
+    );
+    assert!(!files.is_empty());
+
+    for path in files {
+        assert_eq!(
+            conformance::run_path(&path, true).unwrap(),
+            conformance::run_path(&path, false).unwrap(),
+            "{}",
+            path.display()
+        );
+    }
+}
+
+#[test]
+fn folds_constant_expressions() {
+    assert_eq!(
+        optimized("print (1 + 2) * -3 == -9;\nprint \"a\" + \"b\";\nprint !nil;\nprint 7 % 4;"),
+        "print true;\nprint \"ab\";\nprint true;\nprint 3;\n"
+    );
+    assert_eq!(
+        optimized("print (a + 1) * (2 * 3);\nprint nil or a;\nprint 1 and a;\nprint a or 2 * 2;"),
+        "print (a + 1) * 6;\nprint a;\nprint a;\nprint a or 4;\n"
+    );
+}
+
+#[test]
+fn keeps_map_statements_from_becoming_blocks() {
+    assert_eq!(
+        optimized("({\"a\": 1 + 1})[\"a\"];\n({}).keys().len() + 1;"),
+        "({\"a\": 2}[\"a\"]);\n({}.keys().len() + 1);\n"
+    );
+}
+
+#[test]
+fn leaves_errors_for_runtime() {
+    let source = "print -\"a\";\nprint 1 + nil;\nprint \"a\" < \"b\";\n";
+    assert_eq!(optimized(source), source);
+}
+
+#[test]
+fn removes_dead_branches() {
+    assert_eq!(
+        optimized("if (1 < 2) print 1; else print 2;\nif (nil) print 3;\nwhile (!true) print 4;"),
+        "print 1;\n"
+    );
+    assert_eq!(
+        optimized("while (a) if (false) print 1;\nif (a) while (false) {} else print 2;"),
+        "while (a) {}\nif (a) {} else print 2;\n"
+    );
+    assert_eq!(
+        optimized("for (var i = 0; i < 2 * 3; i = i + 1) { if (i < 1 + 1) continue; break; }"),
+        "{\n  var i = 0;\n  for (; i < 6; i = i + 1) {\n    if (i < 2) continue;\n    break;\n  }\n}\n"
+    );
+}
+
+#[test]
+fn keeps_the_value_of_eval() {
+    for optimize in [false, true] {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_optimize(optimize);
+        assert!(matches!(
+            interpreter.eval("1 + 2; if (false) 3;").unwrap(),
+            Value::Nil
+        ));
+        assert!(matches!(
+            interpreter.eval("1 + 2 * 3;").unwrap(),
+            Value::Number(n) if n == 7.0
+        ));
+    }
+}